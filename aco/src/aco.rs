@@ -1,159 +1,205 @@
-use crate::ant2::{Ant, argmax};
+use crate::ant2::{Ant, EdgeCounts};
 use crate::city::City;
-use crate::graph::{Graph, get_tour_tuples, get_tour_length_generic};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use crate::config::{AcoConfig, ConfigError};
+use crate::error::AcoError;
+use crate::rng::{AntRng, colony_rng, ant_rngs};
+use crate::stop::{Progress, StopReason};
+use crate::strategy::DepositSchedule;
+use crate::graph::{Graph, create_heuristic_graph, get_tour_tuples, get_tour_length_graph};
+use std::sync::{Arc, Mutex, MutexGuard};
+use rayon::ThreadPool;
 use rayon::prelude::*;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ACO  {
     pub best_path: Vec<City>,
     pub best_path_distance: f32,
-    alpha: f32,
-    iterations: i32,
-    num_ants: i32,
-    cities_list: &'static Vec<City>,
-    pheromone_graph: &'static Arc<Mutex<Graph>>,
-    distance_graph: &'static Graph,
+    config: AcoConfig,
+    cities_list: Vec<City>,
+    pheromone_graph: Arc<Mutex<Graph>>,
+    heuristic_graph: Arc<Graph>,
+    distance_graph: Arc<Graph>,
+    rng: AntRng,
+    deposit: DepositSchedule,
+    iteration_best_path: Vec<City>,
+    iteration_best_distance: f32,
+    stop_reason: Option<StopReason>,
+    iterations_run: usize,
+    pool: Option<Arc<ThreadPool>>,
 }
 
 impl ACO {
-    pub fn new(cities_list: &'static Vec<City>, 
-               pheromone_graph: &'static Arc<Mutex<Graph>>, 
-               distance_graph: &'static Graph, 
-               num_ants: i32,
-               iterations: i32) -> Self {
-        Self {
+    pub fn new(cities_list: Vec<City>, 
+               pheromone_graph: Arc<Mutex<Graph>>, 
+               distance_graph: Graph, 
+               config: AcoConfig) -> Result<Self, AcoError> {
+        // The colony owns its cities, pheromone and distances, every tour length is looked up in distance_graph
+        config.validate()?;
+        let rng:AntRng = colony_rng(config.seed());
+        let heuristic_graph:Arc<Graph> = Arc::new(create_heuristic_graph(&distance_graph, config.beta()));
+        let distance_graph:Arc<Graph> = Arc::new(distance_graph);
+        let pool:Option<Arc<ThreadPool>> = config.thread_pool()?;
+        Ok(Self {
             best_path: Vec::new(),
-            best_path_distance: std::f32::INFINITY,
-            pheromone_graph: pheromone_graph,
-            distance_graph: distance_graph,
-            num_ants: num_ants,
-            cities_list: cities_list,
-            iterations: iterations,
-            alpha: 0.1,
+            best_path_distance: f32::INFINITY,
+            pheromone_graph,
+            heuristic_graph,
+            distance_graph,
+            cities_list,
+            config,
+            rng,
+            deposit: DepositSchedule::BestSoFar,
+            iteration_best_path: Vec::new(),
+            iteration_best_distance: f32::INFINITY,
+            stop_reason: None,
+            iterations_run: 0,
+            pool,
+        })
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        // Why the last call to optimize or optimize_concurrent_rayon stopped, None before the first call
+        self.stop_reason
+    }
+
+    pub fn iterations_run(&self) -> usize {
+        // Iterations completed by the last call to optimize or optimize_concurrent_rayon
+        self.iterations_run
+    }
+
+    pub fn with_deposit(mut self, deposit:DepositSchedule) -> Result<Self, ConfigError> {
+        // Which tour the global update uses, the best-so-far tour by default
+        deposit.validate()?;
+        self.deposit = deposit;
+        Ok(self)
+    }
+
+    fn record_tour(&mut self, tour:&[City], tour_dist:f32) {
+        // Keeps the iteration-best and best-so-far tours up to date
+        if tour_dist < self.iteration_best_distance {
+            self.iteration_best_distance = tour_dist;
+            self.iteration_best_path = tour.to_vec();
+        }
+        if tour_dist < self.best_path_distance {
+            self.best_path_distance = tour_dist;
+            self.best_path = tour.to_vec();
         }
     }
 
 
     pub fn optimize_concurrent(&mut self) {
-        let aco_mutex: Arc<Mutex<ACO>> = Arc::new(Mutex::new(self.clone()));
-
-        println!("\noptimize_concurrent()\n");
-
-        for i in 0..self.iterations{
-            println!("Iteration {}, best_dist-> {}" , i, aco_mutex.lock().unwrap().best_path_distance);
-            let mut handles = vec![];
-            for _ in 0..self.num_ants{
-                let aco_mutex:Arc<Mutex<ACO>> = Arc::clone(&aco_mutex);
-                let handle = thread::spawn({
-                    move ||{
-                    let mut aco_mutex = aco_mutex.lock().unwrap();
-                    let ant = Ant::new(&aco_mutex.cities_list, &aco_mutex.pheromone_graph, &aco_mutex.distance_graph);
-                    let tour:Vec<City> = ant.make_tour();
-                    let tour2:Vec<City> = ant.two_opt(&tour);
-                    ant.local_pheromone_update(&tour);
-                    let tour_dist:f32 = get_tour_length_generic(tour2.to_vec());
-                    if tour_dist < aco_mutex.best_path_distance {
-                        aco_mutex.best_path_distance = tour_dist;
-                        aco_mutex.best_path = tour2;
-                        }
-                    } 
-                });
-                handles.push(handle);
-            }
-            for handle in handles{
-                handle.join().unwrap();
-            }
-            let mut aco_mutex = aco_mutex.lock().unwrap();
-            aco_mutex.global_update_pheromone();
+        // Used to spawn a thread per ant, but each one held the colony lock for its whole tour so they took turns.
+        // Kept for existing callers, the parallel mode is optimize_concurrent_rayon()
+        self.optimize_concurrent_rayon(Vec::new());
+    }
+
+
+    fn merge_local_updates(&self, edge_counts:&EdgeCounts) {
+        /* Applies the local update of every ant at once. An edge crossed k times decays k times towards tau,
+           (1 - rho)^k * old + (1 - (1 - rho)^k) * tau, the same as updating after each crossing in any order */
+        let mut pher_graph:MutexGuard<Graph> = self.pheromone_graph.lock().unwrap();
+        for ((from_city, to_city), count) in edge_counts.iter() {
+            let kept:f32 = f32::powi(1.0 - self.config.rho(), *count as i32);
+            let pheromone:&mut f32 = pher_graph.get_mut(from_city).and_then(|to_cities| to_cities.get_mut(to_city))
+                                               .expect("Couldn't find city in merge_local_updates()");
+            *pheromone = kept * *pheromone + (1.0 - kept) * self.config.tau();
         }
     }
 
     
-    fn global_update_pheromone(&mut self) {
-        // Takes the best tour and applies the global update pheromone 
+    fn global_update_pheromone(&mut self, iteration:usize) {
+        // Takes the best tour picked by the deposit schedule and applies the global update pheromone
+        // to its edges, then starts the next iteration's search for an iteration-best tour
+        let (tour, tour_dist) = if self.deposit.uses_best_so_far(iteration) || self.iteration_best_path.is_empty() {
+            (&self.best_path, self.best_path_distance)
+        } else {
+            (&self.iteration_best_path, self.iteration_best_distance)
+        };
         let mut pher_graph = self.pheromone_graph.lock().unwrap();
-        let tour_city_tuples:Vec<(City, City)> = get_tour_tuples(&self.best_path);
+        let tour_city_tuples:Vec<(City, City)> = get_tour_tuples(tour);
         for (from_city,to_city) in tour_city_tuples {
             let old_pheromone:f32 = *pher_graph.get(&from_city).unwrap().get(&to_city).unwrap();
-            let new_pheromone:f32 = (1.0 - self.alpha) * old_pheromone + self.alpha * f32::powf(self.best_path_distance, -1.0);
+            let new_pheromone:f32 = (1.0 - self.config.alpha()) * old_pheromone + self.config.alpha() * f32::powf(tour_dist, -1.0);
             if let Some(from_city_name) = pher_graph.get_mut(&from_city){
                 from_city_name.insert(to_city, new_pheromone);
             }
         }
+        drop(pher_graph);
+        self.iteration_best_path.clear();
+        self.iteration_best_distance = f32::INFINITY;
     }
 
     pub fn optimize(&mut self, short_path:Vec<&City>) {
-        
-        let short_path_dist:f32 = get_tour_length_generic(short_path);
-
+        // Builds the ants' tours one after another, giving the same tours as optimize_concurrent_rayon()
         println!("\noptimize()\n");
-
-        for i in 0..self.iterations {
-            
-            println!("Iteration {:?}, best distance found -> {:.2}, shortest_path_distance -> {:.2}", i, self.best_path_distance, short_path_dist);
-            
-            if short_path_dist == self.best_path_distance {
-                println!("\nShort path found at {} iteration", i);
-                break;
-            }
-            
-            let ants:Vec<Ant> = (0..self.num_ants).map(|_|Ant::new(self.cities_list, self.pheromone_graph, self.distance_graph)).collect();
-            let tours:Vec<Vec<City>> = ants.iter().map(|ant| ant.make_tour()).collect();  
-            for (ant, tour) in ants.iter().zip(tours.iter()) {
-                let new_tour = ant.two_opt(tour);
-                ant.local_pheromone_update(&new_tour);
-            }
-
-            let tour_dists:Vec<f32> = tours.iter().map(|tour| get_tour_length_generic(tour.to_vec())).collect();
-            for (tour, dist) in tours.iter().zip(tour_dists) {
-                if dist < self.best_path_distance {
-                    self.best_path_distance = dist;
-                    self.best_path = tour.clone();    
-                } 
-            }
-            self.global_update_pheromone();
-        }
+        self.run(short_path, None);
     }
 
 
-
     pub fn optimize_concurrent_rayon(&mut self, short_path:Vec<&City>) {
-        let short_path_dist:f32 = get_tour_length_generic(short_path);
-        
+        // Builds the ants' tours on the colony's pool, sequentially when the config is not parallel
         println!("\noptimize_concurrent_rayon()\n");
+        let pool:Option<Arc<ThreadPool>> = self.pool.clone();
+        self.run(short_path, pool.as_deref());
+    }
+
+
+    fn run(&mut self, short_path:Vec<&City>, pool:Option<&ThreadPool>) {
+        let short_path_dist:f32 = get_tour_length_graph(&short_path, &self.distance_graph);
+        let mut progress:Progress = Progress::new();
+        self.stop_reason = Some(StopReason::Iterations);
         
-        for i in 0..self.iterations {
+        for i in 0..self.config.iterations() {
+
+            if let Some(reason) = self.config.stop_criteria().check_before_iteration(&progress) {
+                println!("\nStopped at {} iteration, {}", i, reason);
+                self.stop_reason = Some(reason);
+                break;
+            }
             
             println!("Iteration {:?}, best dist found -> {:.2}, shortest_path_distance -> {:.2}", i, self.best_path_distance, short_path_dist);
             
             if short_path_dist == self.best_path_distance {
                 println!("\nShort path found at {} iteration", i);
+                self.stop_reason = Some(StopReason::TargetReached);
                 break;
             }
-            let ants:Vec<Ant> = (0..self.num_ants).map(|_|Ant::new(self.cities_list, self.pheromone_graph, self.distance_graph)).collect();
-            let ant_tours:Vec<(usize, Vec<City>)> = ants.par_iter().enumerate().map(|(index, ant)|{
-                let tour = ant.make_tour();
-                let new_tour = ant.two_opt(&tour);
-                ant.local_pheromone_update(&new_tour);
-                (index, new_tour)
-            }).collect();
-            let tour_dists:Vec<f32> = ant_tours.par_iter()
-                                     .map(|(_, tour)| 
-                                     get_tour_length_generic(tour.to_vec()))
-                                     .collect();
-            let max_index:usize = argmax(tour_dists.clone());
-            if self.best_path_distance > tour_dists[max_index] {
-                // let best_tour = ant_tours[max_index].1.clone();
-                self.best_path_distance = tour_dists[max_index];
-                self.best_path = ant_tours[max_index].1.clone();
+            // Ants read a copy of the pheromone taken at the start of the iteration, so they need no lock
+            let pheromone_snapshot:Graph = self.pheromone_graph.lock().unwrap().clone();
+            let num_ants:usize = self.config.stop_criteria().ants_this_iteration(&progress, self.config.num_ants());
+            let ants:Vec<Ant> = (0..num_ants).map(|_|Ant::new(&self.cities_list, &self.pheromone_graph, Arc::clone(&self.heuristic_graph), Arc::clone(&self.distance_graph), &self.config)).collect();
+            let rngs:Vec<AntRng> = ant_rngs(&mut self.rng, num_ants);
+            let add_ant_tour = |(mut tours, mut edge_counts):(Vec<Vec<City>>, EdgeCounts), (ant, mut rng):(&Ant, AntRng)| {
+                let tour:Vec<City> = ant.make_tour_on(&pheromone_snapshot, &mut rng);
+                let new_tour:Vec<City> = ant.two_opt(&tour);
+                ant.count_tour_edges(&new_tour, &mut edge_counts);
+                tours.push(new_tour);
+                (tours, edge_counts)
+            };
+            let (ant_tours, edge_counts):(Vec<Vec<City>>, EdgeCounts) = match pool {
+                Some(pool) => pool.install(|| ants.par_iter().zip(rngs)
+                                                  .fold(|| (Vec::new(), EdgeCounts::new()), add_ant_tour)
+                                                  .reduce(|| (Vec::new(), EdgeCounts::new()), |(mut tours, mut edge_counts), (other_tours, other_counts)| {
+                                                      // Keeps the ants' order, and sums the counts so the merge does not depend on thread scheduling
+                                                      tours.extend(other_tours);
+                                                      for (edge, count) in other_counts {
+                                                          *edge_counts.entry(edge).or_insert(0) += count;
+                                                      }
+                                                      (tours, edge_counts)
+                                                  })),
+                None => ants.iter().zip(rngs).fold((Vec::new(), EdgeCounts::new()), add_ant_tour),
+            };
+            self.merge_local_updates(&edge_counts);
+            for tour in ant_tours.iter() {
+                let tour_dist:f32 = get_tour_length_graph(tour, &self.distance_graph);
+                self.record_tour(tour, tour_dist);
             }
-            self.global_update_pheromone();
+            self.global_update_pheromone(i);
+            progress.record_iteration(num_ants, self.best_path_distance);
         }
-            
-    }    
+        self.iterations_run = progress.iterations();
+    }
 }
 
 
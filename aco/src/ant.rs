@@ -1,21 +1,10 @@
 use std::sync::{Arc, Mutex};
 use rand::Rng;
-use random_choice::random_choice;
 
 use crate::city::City;
+use crate::config::AcoConfig;
 use crate::graph::Graph;
-
-
-fn argmax<T: PartialOrd + Copy>(array:Vec<T>) -> usize {
-    // Returns the index of the maximum value in a vec
-    let mut max_index = 0;
-    for (i, val) in array.iter().enumerate(){
-        if val > &array[max_index]{
-            max_index = i
-        }
-    }
-    max_index
-}
+use crate::rng::{AntRng, roulette_wheel};
 
 
 #[derive(Debug, Clone)]
@@ -25,29 +14,25 @@ pub struct Ant<'a> {
     visited_nodes: Vec<City>,
     pheromone_graph: &'a Arc<Mutex<Graph>>,
     distance_graph: &'a Graph,
-    beta: f32,
-    q0: f32,
-    rho: f32,
-    tau: f32,
+    config: AcoConfig,
+    rng: AntRng,
 }
 
 
 impl<'a> Ant<'a>{
 
-    pub fn new(cities_list: &'a Vec<City>, pheromone_graph: &'a Arc<Mutex<Graph>>, distance_graph:&'a Graph) -> Self {
-        // Creates a new Ant object by choosing the first city in the cities list  
+    pub fn new(cities_list: &'a Vec<City>, pheromone_graph: &'a Arc<Mutex<Graph>>, distance_graph:&'a Graph, config:&AcoConfig, mut rng:AntRng) -> Self {
+        // Creates a new Ant object by choosing a random first city in the cities list  
         // and adding it to the visited cities list
-        let rand_index = rand::thread_rng().gen_range(0..cities_list.len()-1);
+        let rand_index = rng.gen_range(0..cities_list.len());
         let start_city:&City = &cities_list[rand_index];
-        let mut ant = Self{cities_list:cities_list,
+        let mut ant = Self{cities_list,
                                 current_node:start_city,
                                 visited_nodes:Vec::new(),  
-                                pheromone_graph:pheromone_graph,
-                                distance_graph:distance_graph,
-                                beta:2.0, 
-                                q0:0.9, 
-                                rho:0.1, 
-                                tau:0.005};
+                                pheromone_graph,
+                                distance_graph,
+                                config:config.clone(),
+                                rng};
         ant.visit_city(start_city.name);
         ant
     }
@@ -64,13 +49,13 @@ impl<'a> Ant<'a>{
         // Adds the given city_name as the current 
         // and adds it to visited nodes list
         let city = self.get_city(city_name).unwrap();
-        self.current_node = &city;
+        self.current_node = city;
         self.visited_nodes.push(*city);
         // println!("visiting city -> {:?}", city);
     }
     
 
-    fn get_unvisited_cities(&self) -> Vec<&City> {
+    fn get_unvisited_cities(&self) -> Vec<&'a City> {
         // Returns a vec of unvisited cities 
         // Used in run_ant_run()
         self.cities_list
@@ -83,23 +68,24 @@ impl<'a> Ant<'a>{
     fn score_node(&self, node_name:i32) -> f32 {
         // Scores a node based on the current node and node_name passed
         // Used in choose_node()
-        let pher_graph_clone = Arc::clone(&self.pheromone_graph);
+        let node:&City = self.get_city(node_name).unwrap();
+        let pher_graph_clone = Arc::clone(self.pheromone_graph);
         let pher_graph = pher_graph_clone.lock().unwrap(); 
-        let phermone = pher_graph.get(&self.current_node.name).unwrap().get(&node_name).unwrap();
-        let distance = self.distance_graph.get(&self.current_node.name).unwrap().get(&node_name).unwrap();
-        phermone * f32::powf(1.0/distance, self.beta)
+        let phermone = pher_graph.get(self.current_node).unwrap().get(node).unwrap();
+        let distance = self.distance_graph.get(self.current_node).unwrap().get(node).unwrap();
+        phermone * f32::powf(1.0/distance, self.config.beta())
     }
 
 
 
 
-    fn choose_node(&self, univisted:Vec<&City>) -> i32 {
+    fn choose_node(&mut self, univisted:Vec<&'a City>) -> i32 {
         // q -> random value between 0,1
-        let q:f32 = rand::thread_rng().gen();
+        let q:f32 = self.rng.gen();
         // let univisted = self.get_unvisited_cities();
         let scores:Vec<f32> = univisted.iter().map(|city| self.score_node(city.name)).collect(); 
-        let mut max_index:usize = std::usize::MAX;
-        let mut max_score:f32 = std::f32::NEG_INFINITY; 
+        let mut max_index:usize = usize::MAX;
+        let mut max_score:f32 = f32::NEG_INFINITY; 
         for (i, score) in scores.iter().enumerate(){
             if score > &max_score {
                 max_score = *score;
@@ -109,17 +95,13 @@ impl<'a> Ant<'a>{
         
         // let max3 = scores.iter().enumerate().fold((-10000, 0.0), |max, (ind, &val)| if val > max.1 {(ind, val)} else {max});
         
-        if q < self.q0 {
+        if q < self.config.q0() {
             // println!("exploiting, node -> {:?}", univisted[max_index].name);
-            return univisted[max_index].name;    
+            univisted[max_index].name
         } else {
-
-            let sum_scores:f32 = scores.iter().sum();
-            let prob_dist:Vec<f32> = scores.iter().map(|score| score/sum_scores).collect();
-            let city_names = &univisted.into_iter().map(|city| city.name).collect::<Vec<_>>();
-            let choice:Vec<&_> = random_choice().random_choice_f32(&city_names, &prob_dist, 1);
-            // println!("exploring, node -> {:?}", **choice.first().unwrap());
-            return **choice.first().unwrap();
+            let choice:usize = roulette_wheel(&scores, &mut self.rng);
+            // println!("exploring, node -> {:?}", univisted[choice].name);
+            univisted[choice].name
         }
     }
 
@@ -127,11 +109,12 @@ impl<'a> Ant<'a>{
     fn local_pheromone_update(&mut self, city_name:i32) {
         // Applies pheromone update to the edge between the current node and the city passed
         // using the previous pheromone and the local pheromone update formula 
+        let city:City = *self.get_city(city_name).unwrap();
         let mut pher_graph = self.pheromone_graph.lock().unwrap();
-        let old_pheromone:f32 = *pher_graph.get(&self.current_node.name).unwrap().get(&city_name).unwrap();
-        let new_pheromone:f32 = (1.0 - self.rho) * old_pheromone + (self.rho * self.tau);
-        if let Some(to_city) = pher_graph.get_mut(&self.current_node.name) {
-            to_city.insert(city_name, new_pheromone);
+        let old_pheromone:f32 = *pher_graph.get(self.current_node).unwrap().get(&city).unwrap();
+        let new_pheromone:f32 = (1.0 - self.config.rho()) * old_pheromone + (self.config.rho() * self.config.tau());
+        if let Some(to_city) = pher_graph.get_mut(self.current_node) {
+            to_city.insert(city, new_pheromone);
         }
     }
 
@@ -147,7 +130,7 @@ impl<'a> Ant<'a>{
         // Close the loop -> connect first and last city
         let start_node:i32 = self.visited_nodes.first().unwrap().name;
         self.local_pheromone_update(start_node);
-        return self.visited_nodes.clone();
+        self.visited_nodes.clone()
     }
 
 } 
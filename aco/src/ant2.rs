@@ -1,12 +1,17 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex, MutexGuard};
 use rand::Rng;
-use random_choice::random_choice;
 use crate::city::City;
-use crate::graph::{Graph, get_tour_tuples_generic, get_tour_length_generic};
+use crate::config::AcoConfig;
+use crate::rng::roulette_wheel;
+use crate::graph::{Graph, get_tour_tuples, get_tour_tuples_generic};
 
+// How many times each edge was crossed, the local update deltas of one thread
+pub type EdgeCounts = HashMap<(City, City), u32>;
 
-pub fn argmax<T: PartialOrd + Copy>(array:Vec<T>) -> usize {
-    // Returns the index of the maximum value in a vec
+
+pub fn argmax<T: PartialOrd + Copy>(array:&[T]) -> usize {
+    // Returns the index of the maximum value in a slice
     let mut max_index = 0;
     for (i, val) in array.iter().enumerate(){
         if val > &array[max_index]{
@@ -17,92 +22,94 @@ pub fn argmax<T: PartialOrd + Copy>(array:Vec<T>) -> usize {
 }
 
 
-fn two_opt_swap<T: Clone>(tour: Vec<T>, i: usize, j: usize) -> Vec<T> {
-    // Takes two index values i, k
-    // Takes the start node to i+1 in a tour and adds it to new tour
-    // Takes all nodes from i+1 to k and adds them in reverse order to the new tour
-    // Takes nodes k+1 to the end of the end of the tour and adds them to new tour 
-    let new_tour: Vec<T> = tour[0..=i].iter()
-        .chain(tour[i+1..=j].iter().rev())
-        .chain(tour[j+1..tour.len()].iter())
-        .cloned().collect();
-    new_tour
-}
-
-
 #[derive(Debug, Clone)]
 pub struct Ant<'a>{
     cities_list:&'a Vec<City>,
     pheromone_graph:&'a Arc<Mutex<Graph>>,
-    distance_graph:&'a Graph,
-    beta: f32,
-    q0: f32,
-    rho: f32,
-    tau: f32,
+    heuristic_graph:Arc<Graph>,
+    distance_graph:Arc<Graph>,
+    config: AcoConfig,
 }
 
 
 impl <'a>Ant<'a>{
-    pub fn new(cities_list:&'a Vec<City>, pheromone_graph:&'a Arc<Mutex<Graph>>,distance_graph: &'a Graph) -> Self {
-        Self{cities_list:cities_list,
-             pheromone_graph:pheromone_graph,
-             distance_graph:distance_graph,
-             beta:2.0, 
-             q0:0.9, 
-             rho:0.1, 
-             tau:0.0005}
+    pub fn new(cities_list:&'a Vec<City>, pheromone_graph:&'a Arc<Mutex<Graph>>, heuristic_graph:Arc<Graph>, distance_graph:Arc<Graph>, config:&AcoConfig) -> Self {
+        // heuristic_graph holds (1 / distance)^beta, see create_heuristic_graph(), two_opt() measures tours on distance_graph
+        Self{cities_list,
+             pheromone_graph,
+             heuristic_graph,
+             distance_graph,
+             config:config.clone()}
     }
 
 
-    fn score_node(&self, from_node:&City, to_node:&City) -> f32 {
+    fn score_node(&self, pher_graph:&Graph, from_node:&City, to_node:&City) -> f32 {
         // Scores a node based on the current node and node_name passed
-        // Used in make_tour()
-        let pher_graph_clone:Arc<Mutex<Graph>> = Arc::clone(&self.pheromone_graph);
-        let pher_graph:MutexGuard<Graph> = pher_graph_clone.lock().unwrap(); 
-        let phermone:&f32 = pher_graph.get(&from_node).unwrap().get(&to_node).unwrap();
-        let distance:&f32 = self.distance_graph.get(&from_node).unwrap().get(&to_node).unwrap();
-        phermone * f32::powf(1.0/distance, self.beta)
+        // Used in make_tour_on(), pher_graph is either the locked graph or a snapshot of it
+        let phermone:&f32 = pher_graph.get(from_node).unwrap().get(to_node).unwrap();
+        let heuristic:&f32 = self.heuristic_graph.get(from_node).unwrap().get(to_node).unwrap();
+        phermone * heuristic
+    }
+
+
+    pub fn make_tour<R: Rng>(&self, rng:&mut R) -> Vec<City> {
+        // Holds the pheromone lock for the whole tour, the local updates are applied once every ant is done
+        let pher_graph:MutexGuard<Graph> = self.pheromone_graph.lock().unwrap();
+        self.make_tour_on(&pher_graph, rng)
     }
 
 
-    pub fn make_tour(&self) -> Vec<City> {
+    pub fn make_tour_on<R: Rng>(&self, pher_graph:&Graph, rng:&mut R) -> Vec<City> {
+        // Builds a tour on the given pheromone, a snapshot lets ants run in parallel without taking the lock
         let mut visited_nodes:Vec<City> = Vec::with_capacity(self.cities_list.len());
         // First city -> start randomly
         // let city_names:Vec<i32> = self.cities_list.iter().map(|city| city.name).collect();
-        let rand_index = rand::thread_rng().gen_range(0..self.cities_list.len()-1);
+        let rand_index = rng.gen_range(0..self.cities_list.len());
         let start_city:City = self.cities_list[rand_index];
         visited_nodes.push(start_city);
+        // Marks cities by their position in cities_list, so finding the unvisited ones is O(n) per step
+        let mut visited:Vec<bool> = vec![false; self.cities_list.len()];
+        visited[rand_index] = true;
+        let mut univisted:Vec<(usize, &City)> = Vec::with_capacity(self.cities_list.len());
+        let mut scores:Vec<f32> = Vec::with_capacity(self.cities_list.len());
         // Rest of cities
         while visited_nodes.len() != self.cities_list.len() {
             
-            let mut univisted:Vec<_> = self.cities_list.iter().filter(|city| !visited_nodes.contains(city)).collect();
-            let scores:Vec<f32> = univisted.iter_mut().map(|city| 
-                                  self.score_node(visited_nodes.last().unwrap(),
-                                  city)).collect();
+            univisted.clear();
+            univisted.extend(self.cities_list.iter().enumerate().filter(|(city_idx, _)| !visited[*city_idx]));
+            scores.clear();
+            scores.extend(univisted.iter().map(|(_, city)| 
+                          self.score_node(pher_graph, visited_nodes.last().unwrap(),
+                          city)));
             assert_eq!(univisted.len(), scores.len(), "unvisited and scores are not equal in length");
-            let q:f32 = rand::thread_rng().gen();
-            if q < self.q0 {
-                let max_index:usize = argmax(scores);
-                visited_nodes.push(*univisted[max_index]);
+            let q:f32 = rng.gen();
+            let choice:usize = if q < self.config.q0() {
+                argmax(&scores)
             } else {
-                let sum_scores:f32 = scores.iter().sum();
-                let prob_dist:Vec<f32> = scores.iter().map(|score| score/sum_scores).collect();
-                // let indices:Vec<_> = (0..=univisted.len()).collect();
-                let indices:Vec<usize> =  (0..univisted.len()).collect();
-                let choice:&usize = random_choice().random_choice_f32(&indices, &prob_dist, 1).first().unwrap();
-                visited_nodes.push(*univisted[*choice]);
-            }
+                roulette_wheel(&scores, rng)
+            };
+            let (city_idx, city) = univisted[choice];
+            visited[city_idx] = true;
+            visited_nodes.push(*city);
         }
         visited_nodes
     }
 
 
-    pub fn local_pheromone_update(&self, tour:&Vec<City>) {
+    pub fn count_tour_edges(&self, tour:&[City], edge_counts:&mut EdgeCounts) {
+        // Records the tour's edges instead of updating the pheromone, see ACO::merge_local_updates()
+        for edge in get_tour_tuples(tour) {
+            *edge_counts.entry(edge).or_insert(0) += 1;
+        }
+    }
+
+
+    pub fn local_pheromone_update(&self, tour:&[City]) {
         let tour_tuples:Vec<(City, City)> = get_tour_tuples_generic(tour.to_vec());
         let mut pher_graph:MutexGuard<Graph> = self.pheromone_graph.lock().unwrap();
         for (from_city, to_city) in tour_tuples {
             let old_pheromone:f32 = *pher_graph.get(&from_city).unwrap().get(&to_city).unwrap();
-            let new_pheromone:f32 = (1.0 - self.rho) * old_pheromone + (self.rho * self.tau);
+            let new_pheromone:f32 = (1.0 - self.config.rho()) * old_pheromone + (self.config.rho() * self.config.tau());
             let to_city_map = pher_graph.get_mut(&from_city)
                                                       .expect("Couldn't find city in local_pheromone_update()");
             to_city_map.insert(to_city, new_pheromone);
@@ -110,33 +117,61 @@ impl <'a>Ant<'a>{
     }
 
 
-    pub fn two_opt(&self, tour:&Vec<City>) -> Vec<City> {
-        // Local search heuristic
+    pub fn two_opt(&self, tour:&[City]) -> Vec<City> {
+        /* Local search heuristic, keeps reversing segments of the best tour while that shortens it
+           and returns a 2-opt local optimum. Segment reversal needs a symmetric distance graph,
+           which every metric ACO builds from coordinates gives */
         let mut best_tour:Vec<City> = tour.to_vec();
-        let mut best_tour_dist:f32 = get_tour_length_generic(best_tour.to_vec()); 
-        let mut iterations_since_improvement:usize = 0;
-        let mut improved:bool = true;   
-        while improved && iterations_since_improvement < 10 {
+        let n:usize = best_tour.len();
+        let distance = |from_city:&City, to_city:&City| self.distance_graph[from_city][to_city];
+        let mut improved:bool = true;
+        while improved {
             improved = false;
-            for i in 0..(tour.len()-1){
-                for j in i + 1..tour.len(){
-                    let new_tour:Vec<City> = two_opt_swap(tour.to_vec(), i, j);
-                    let new_tour_dist = get_tour_length_generic(new_tour.to_vec());
-                    if new_tour_dist < best_tour_dist {
-                        best_tour_dist = new_tour_dist;
-                        best_tour = new_tour;
+            for i in 0..n.saturating_sub(1) {
+                for j in i + 1..n {
+                    // Replaces edges (i, i + 1) and (j, j + 1) with (i, j) and (i + 1, j + 1)
+                    let (a, b, c, d) = (&best_tour[i], &best_tour[i + 1], &best_tour[j], &best_tour[(j + 1) % n]);
+                    let delta:f32 = distance(a, c) + distance(b, d) - distance(a, b) - distance(c, d);
+                    if delta < -1e-4 {
+                        best_tour[i + 1..=j].reverse();
                         improved = true;
-                        iterations_since_improvement = 0;
-                        break;
                     }
                 }
-            if improved {
-                break;
             }
         }
-        iterations_since_improvement += 1;
-        }
-        best_tour.clone()
+        best_tour
     }
     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{create_pheromone_graph, create_distance_graph, create_heuristic_graph, get_tour_length_graph};
+    use crate::metric::DistanceMetric;
+
+    use super::*;
+
+    #[test]
+    fn two_opt_stops_at_a_local_optimum() {
+        // Scattered cities in a scrambled order need several moves, each building on the last
+        let cities:Vec<City> = (0..12).map(|i| City{name:i + 1, x:(i * 37 % 101) as f64, y:(i * 59 % 97) as f64}).collect();
+        let distance_graph:Arc<Graph> = Arc::new(create_distance_graph(&cities, DistanceMetric::Euclidean));
+        let heuristic_graph:Arc<Graph> = Arc::new(create_heuristic_graph(&distance_graph, 2.0));
+        let pheromone_graph:Arc<Mutex<Graph>> = create_pheromone_graph(&cities, 0.0005);
+        let ant = Ant::new(&cities, &pheromone_graph, heuristic_graph, Arc::clone(&distance_graph), &AcoConfig::default());
+        let tour:Vec<City> = [0, 5, 2, 9, 4, 11, 1, 7, 3, 10, 6, 8].iter().map(|i| cities[*i]).collect();
+        let improved:Vec<City> = ant.two_opt(&tour);
+        let improved_length:f32 = get_tour_length_graph(&improved, &distance_graph);
+        assert!(improved_length < get_tour_length_graph(&tour, &distance_graph));
+        let mut sorted:Vec<i32> = improved.iter().map(|city| city.name).collect();
+        sorted.sort();
+        assert_eq!(sorted, (1..=12).collect::<Vec<i32>>());
+        for i in 0..cities.len() - 1 {
+            for j in i + 1..cities.len() {
+                let mut neighbour:Vec<City> = improved.clone();
+                neighbour[i + 1..=j].reverse();
+                assert!(get_tour_length_graph(&neighbour, &distance_graph) > improved_length - 1e-3);
+            }
+        }
+    }
+}
@@ -1,97 +1,107 @@
-use std::fs;
 use rand::Rng;
-use random_choice::random_choice;
+use rayon::ThreadPool;
+use rayon::prelude::*;
 use std::borrow::Borrow;
+use std::sync::Arc;
 
-use crate::city::City;
+use crate::candidates::CandidateLists;
+use crate::city::{City, parse_coordinates};
+use crate::error::{AcoError, read_file, parse_token, token_column};
+use crate::metric::DistanceMetric;
+use crate::matrix::Matrix;
+use crate::choice::ChoiceInfo;
+use crate::local_search::{LocalSearch, NoLocalSearch, OrOptDirected};
+use crate::config::AcoConfig;
+use crate::stop::{Progress, StopReason};
+use crate::strategy::{PheromoneStrategy, AntColonySystem, StepUpdate, UpdateContext, IterationTours};
+use crate::tour::Tour;
+use crate::rng::{AntRng, colony_rng, ant_rngs, roulette_wheel};
 
-pub type GraphMatrix = Vec<Vec<f32>>;
+pub type GraphMatrix = Matrix<f32>;
 
 
-pub fn calculate_distance_generic<T>(city1:T, city2:T) -> f32 
+pub fn calculate_distance_generic<T>(city1:T, city2:T, metric:DistanceMetric) -> f32 
 where 
     T: Borrow<City>,
 { 
-    let city1:&City = city1.borrow();
-    let city2:&City = city2.borrow();
-    (((city1.x - city2.x) as f32).powf(2.0) + ((city1.y - city2.y) as f32).powf(2.0)).sqrt()
-}
-
-pub fn cities_from_coordinates3(file_path:&str) -> Vec<City> {
-    /* Takes a file name and returns a vec of City objects */ 
-    let mut cities:Vec<City> = Vec::new();
-    
-    let file = fs::read_to_string(file_path).expect("Failed to read file");
-    for (i, line) in file.lines().enumerate(){
-        let coords:Vec<&str> = line.split(",").map(|s| s.trim()).collect();
-        if let (Ok(x), Ok(y)) = (coords[0].parse::<i32>(), coords[1].parse::<i32>()) {
-            let city = City{name:(i+1) as i32, x:x, y:y};
-            cities.push(city);
-        };
-    }
-    cities
+    metric.distance(city1.borrow(), city2.borrow())
+}
+
+pub fn cities_from_coordinates3(file_path:&str) -> Result<Vec<City>, AcoError> {
+    /* Takes a file name and returns a vec of City objects
+       Blank lines are skipped, any other line that is not "x, y" is an error */ 
+    let file = read_file(file_path)?;
+    let cities:Vec<City> = parse_coordinates(&file)?
+                           .into_iter()
+                           .enumerate()
+                           .map(|(i, (x, y))| City{name:(i+1) as i32, x, y})
+                           .collect();
+    Ok(cities)
 }
 
 
-pub fn get_short_path_indicies(file_path:&str) -> Vec<usize> {
+pub fn get_short_path_indicies(file_path:&str) -> Result<Vec<usize>, AcoError> {
     /*  Takes a file path a returns the index values of the shortest path
         The file contains the name of cities starting from 1 
         The indicies start from zero 
     */
-    let file = fs::read_to_string(file_path).expect("Failed to read file");
-    let shortest_path_idx :Vec<usize> = file.split_whitespace()
-                                    .map(|s| 
-                                    s.trim()
-                                    .parse::<usize>().unwrap() -1)
-                                    .collect();
-    shortest_path_idx
+    let file = read_file(file_path)?;
+    let mut shortest_path_idx:Vec<usize> = Vec::new();
+    for (line_idx, line) in file.lines().enumerate() {
+        for token in line.split_whitespace() {
+            let city_name:usize = parse_token(line_idx, line, token, "city number")?;
+            if city_name == 0 {
+                return Err(AcoError::Parse{line:line_idx + 1, column:token_column(line, token),
+                                           message:"city numbers start from 1, found 0".to_string()});
+            }
+            shortest_path_idx.push(city_name - 1);
+        }
+    }
+    Ok(shortest_path_idx)
 }
 
 
-fn get_connected_cities_indicies(city_index:usize, cities_list:&Vec<City>) -> Vec<usize> {
-    // Given a city_index returns a vec of all connected cities
+fn get_connected_cities_indicies(city_index:usize, cities_list:&[City]) -> impl Iterator<Item = usize> {
+    // Given a city_index returns all connected cities, without collecting them into a vec
     assert!(city_index < cities_list.len(), "city_index provided is out of bounds");
-    let cities_idx:Vec<usize> = (0..cities_list.len()).collect();
-    let connected_idx:Vec<usize> = cities_idx.iter().filter(|c| *c!= &city_index).cloned().collect::<Vec<usize>>();
-    connected_idx 
+    (0..cities_list.len()).filter(move |c| *c != city_index)
 }
 
 
-pub fn get_pheromone_graph(cities_list:&Vec<City>, initial_pheromone_value:f32) -> GraphMatrix {
+pub fn get_pheromone_graph(cities_list:&[City], initial_pheromone_value:f32) -> GraphMatrix {
     // Takes a vec of City and initial pheromone value and creates a pheromone matrix
-    let mut pher_graph:Vec<Vec<f32>> = vec![vec![0.0; cities_list.len()]; cities_list.len()];
-    for from_city_idx in 0..cities_list.len(){
-        for to_city_idx in get_connected_cities_indicies(from_city_idx, cities_list){
-            pher_graph[from_city_idx][to_city_idx] = initial_pheromone_value;
-        }
-    }
-    pher_graph
+    // Stored in full as the pheromone on (i, j) and (j, i) can differ
+    GraphMatrix::from_fn(cities_list.len(), |from_city_idx, to_city_idx|
+        if from_city_idx == to_city_idx {0.0} else {initial_pheromone_value})
 }
 
 
-pub fn get_distance_graph(cities_list:&Vec<City>) -> GraphMatrix {
-    // Creates a distance matrix betweeen all connected cities
-    let mut dist_graph:Vec<Vec<f32>> = vec![vec![0.0; cities_list.len()]; cities_list.len()];
-    for from_city_idx in 0..cities_list.len(){
-        for to_city_idx in get_connected_cities_indicies(from_city_idx, cities_list){
-            dist_graph[from_city_idx][to_city_idx] = 
-            calculate_distance_generic(&cities_list[from_city_idx], &cities_list[to_city_idx]);
-        }
+pub fn get_distance_graph(cities_list:&[City], metric:DistanceMetric) -> GraphMatrix {
+    // Creates a distance matrix betweeen all connected cities using the given metric
+    // Every metric is symmetric so only the upper triangle is stored
+    GraphMatrix::from_fn_symmetric(cities_list.len(), |from_city_idx, to_city_idx|
+        if from_city_idx == to_city_idx {0.0} else {metric.distance(&cities_list[from_city_idx], &cities_list[to_city_idx])})
+}
+
+
+pub(crate) fn check_square(graph:&GraphMatrix, num_cities:usize) -> Result<(), AcoError> {
+    // Checks the matrix has one row and one column per city
+    if graph.size() != num_cities {
+        return Err(AcoError::DimensionMismatch{expected:num_cities, found:graph.size()});
     }
-    dist_graph
+    Ok(())
 }
 
 
-fn score_city(from_city_idx:usize, to_city_idx:usize, pher_graph:&GraphMatrix, dist_graph:&GraphMatrix, beta:f32) -> f32 {
-    // Scores the to_city_idx based on from_city_idx
-    let beta:f32 = if beta!=0.0 {beta} else{0.20};
-    let score:f32 = pher_graph[from_city_idx][to_city_idx] * f32::powf(1.0/dist_graph[from_city_idx][to_city_idx], beta);
-    score
+pub fn is_symmetric(dist_graph:&GraphMatrix) -> bool {
+    // Returns true if the cost of every edge is the same in both directions
+    dist_graph.is_symmetric_storage() ||
+    (0..dist_graph.size()).all(|i| (i + 1..dist_graph.size()).all(|j| dist_graph[(i, j)] == dist_graph[(j, i)]))
 }
 
 
-pub fn argmax<T: PartialOrd + Copy>(array:Vec<T>) -> usize {
-    // Returns the index of the maximum value in a vec
+pub fn argmax<T: PartialOrd + Copy>(array:&[T]) -> usize {
+    // Returns the index of the maximum value in a slice, the first one on ties
     let mut max_index = 0;
     for (i, val) in array.iter().enumerate(){
         if val > &array[max_index]{
@@ -102,119 +112,437 @@ pub fn argmax<T: PartialOrd + Copy>(array:Vec<T>) -> usize {
 }
 
 
-pub fn build_tour(cities_list:&Vec<City>, pher_graph:&GraphMatrix, dist_graph:&GraphMatrix, q0:f32, beta:f32) -> Vec<usize>{
-    let cities_idx:Vec<usize> = (0..cities_list.len()).into_iter().collect();
-    let mut tour:Vec<usize> = Vec::with_capacity(cities_list.len());
-    let start_city_idx = rand::thread_rng().gen_range(0..cities_list.len()-1);
-    tour.push(start_city_idx);
-    for _ in 1..cities_idx.len() {
-        let unvisited: Vec<&usize> = cities_idx.iter().filter(|&city| !tour.contains(city)).collect();
-        let scores: Vec<f32> = unvisited.iter().map(|&to_city_idx| 
-                                score_city(*tour.last().unwrap(), *to_city_idx, pher_graph, dist_graph, beta)).collect::<Vec<f32>>();
-        let q:f32 = rand::thread_rng().gen();
-        if q < q0 {
-            let max_index:usize = argmax(scores);
-            tour.push(*unvisited[max_index]);
-        } else{
-            let sum_scores:f32 = scores.iter().sum();
-            let prob_dist:Vec<f32> = scores.iter().map(|score| score/sum_scores).collect();
-            // let indices:Vec<_> = (0..=univisted.len()).collect();
-            let indices:Vec<usize> =  (0..unvisited.len()).collect();
-            let choice:&usize = random_choice().random_choice_f32(&indices, &prob_dist, 1).first().unwrap();
-            tour.push(*unvisited[*choice]);
+#[derive(Debug, Clone, Default)]
+pub struct TourBuilder {
+    visited: Vec<bool>,
+    unvisited: Vec<usize>,
+    scores: Vec<f32>,
+}
+
+
+impl TourBuilder {
+
+    pub fn new(num_cities:usize) -> TourBuilder {
+        // Scratch space for one ant, kept between tours so construction does not allocate per step
+        TourBuilder{visited:Vec::with_capacity(num_cities), unvisited:Vec::with_capacity(num_cities), scores:Vec::with_capacity(num_cities)}
+    }
+
+    fn start<R: Rng>(&mut self, num_cities:usize, rng:&mut R) -> Vec<usize> {
+        // Clears the visited marks and places the ant on a random city
+        let mut tour:Vec<usize> = Vec::with_capacity(num_cities);
+        self.visited.clear();
+        self.visited.resize(num_cities, false);
+        let start_city_idx = rng.gen_range(0..num_cities);
+        tour.push(start_city_idx);
+        self.visited[start_city_idx] = true;
+        tour
+    }
+
+    fn next_city<R: Rng>(&mut self, choice_info:&ChoiceInfo, from_city_idx:usize, q0:f32,
+                         candidates:Option<&CandidateLists>, rng:&mut R) -> usize {
+        // Picks and marks the city the ant moves to from from_city_idx
+        let num_cities:usize = self.visited.len();
+        assert!(num_cities == choice_info.size() && from_city_idx < num_cities, "city {} out of bounds for {} cities", from_city_idx, num_cities);
+        let visited:&[bool] = &self.visited;
+        self.unvisited.clear();
+        if let Some(candidates) = candidates {
+            self.unvisited.extend(candidates.neighbours(from_city_idx).iter().filter(|city| !visited[**city]));
+        }
+        if self.unvisited.is_empty() {
+            self.unvisited.extend((0..num_cities).filter(|city| !visited[*city]));
+        }
+        self.scores.clear();
+        // Every unvisited city indexed visited above, so it is below num_cities
+        self.scores.extend(self.unvisited.iter().map(|to_city_idx| unsafe {choice_info.score_unchecked(from_city_idx, *to_city_idx)}));
+        let q:f32 = rng.gen();
+        let choice:usize = if q < q0 {argmax(&self.scores)} else {roulette_wheel(&self.scores, rng)};
+        let next_city_idx:usize = self.unvisited[choice];
+        self.visited[next_city_idx] = true;
+        next_city_idx
+    }
+
+    pub fn build<R: Rng>(&mut self, choice_info:&ChoiceInfo, q0:f32, candidates:Option<&CandidateLists>, rng:&mut R) -> Vec<usize> {
+        /* With candidate lists an ant only looks at the unvisited candidates of its city,
+           and at every unvisited city once those are all used. Scores are looked up in choice_info,
+           which already holds pheromone * eta^beta. Unvisited cities are listed in index order,
+           so a seed gives the same tours whatever buffers were used before */
+        let mut tour:Vec<usize> = self.start(choice_info.size(), rng);
+        for _ in 1..choice_info.size() {
+            let next_city_idx:usize = self.next_city(choice_info, *tour.last().unwrap(), q0, candidates, rng);
+            tour.push(next_city_idx);
+        }
+        tour
+    }
+
+    pub fn build_with_step_update<R: Rng>(&mut self, pher_graph:&mut GraphMatrix, choice_info:&mut ChoiceInfo, step_update:&StepUpdate,
+                                          q0:f32, candidates:Option<&CandidateLists>, rng:&mut R) -> Vec<usize> {
+        // Same as build, and every edge the ant crosses, including the one back to the start, is
+        // decayed as soon as it is crossed so the rest of the tour and later ants see it
+        let mut tour:Vec<usize> = self.start(choice_info.size(), rng);
+        for _ in 1..choice_info.size() {
+            let from_city_idx:usize = *tour.last().unwrap();
+            let next_city_idx:usize = self.next_city(choice_info, from_city_idx, q0, candidates, rng);
+            step_update.apply(pher_graph, choice_info, from_city_idx, next_city_idx);
+            tour.push(next_city_idx);
+        }
+        if tour.len() > 1 {
+            step_update.apply(pher_graph, choice_info, tour[tour.len() - 1], tour[0]);
         }
+        tour
     }
-    assert!(tour.len() == cities_idx.len(), "Tour and cities are not the same length");
+}
+
+
+pub fn build_tour<R: Rng>(cities_list:&[City], choice_info:&ChoiceInfo, q0:f32,
+                          candidates:Option<&CandidateLists>, rng:&mut R) -> Vec<usize>{
+    // Builds one tour with fresh scratch buffers, colonies keep a TourBuilder per ant instead
+    let tour:Vec<usize> = TourBuilder::new(cities_list.len()).build(choice_info, q0, candidates, rng);
+    assert!(tour.len() == cities_list.len(), "Tour and cities are not the same length");
     tour
 }
 
 
-pub fn local_pheromone_update3(mut pher_graph:GraphMatrix, tour:Vec<usize>, cities_list:&Vec<City>, rho:f32, tau:f32) -> GraphMatrix {
-    // Updates the pheromone graph given a tour
-    for from_city_idx in tour{
+pub fn local_pheromone_update3(pher_graph:&mut GraphMatrix, tour:&[usize], cities_list:&[City], rho:f32, tau:f32) {
+    // Updates the pheromone graph given a tour, in place
+    for &from_city_idx in tour{
         for to_city_idx in get_connected_cities_indicies(from_city_idx, cities_list){
-            let old_pher_val:f32 = pher_graph[from_city_idx][to_city_idx];
+            let old_pher_val:f32 = pher_graph[(from_city_idx, to_city_idx)];
             let new_pher_val = (1.0 - rho) * old_pher_val + (rho * tau); 
-            pher_graph[from_city_idx][to_city_idx]=new_pher_val;
+            pher_graph[(from_city_idx, to_city_idx)]=new_pher_val;
         }
     }
-    pher_graph
 }
 
 
-fn global_pheromone_update(mut pher_graph:GraphMatrix, 
-                               tour:Vec<usize>, 
-                               cities_list:&Vec<City>, 
+pub(crate) fn global_pheromone_update(pher_graph:&mut GraphMatrix, 
+                               tour:&[usize], 
+                               cities_list:&[City], 
                                alpha:f32, 
-                               best_path_distance:f32) -> GraphMatrix {
-    for from_city_idx in tour{
+                               best_path_distance:f32) {
+    // Updates the pheromone graph given the best tour, in place
+    for &from_city_idx in tour{
         for to_city_idx in get_connected_cities_indicies(from_city_idx, cities_list){
-            let old_pheromone:f32 = pher_graph[from_city_idx][to_city_idx];
+            let old_pheromone:f32 = pher_graph[(from_city_idx, to_city_idx)];
             let new_pheromone:f32 = (1.0 - alpha) * old_pheromone + alpha * f32::powf(best_path_distance, -1.0);
-            pher_graph[from_city_idx][to_city_idx] = new_pheromone;
+            pher_graph[(from_city_idx, to_city_idx)] = new_pheromone;
         }
     }
-    pher_graph
 }
 
-pub fn get_tour_distance(tour:Vec<usize>, cities_list:&Vec<City>) -> f32 {
-    // Takes a vec of city indicies (tour) and returns the total distance travelled
+pub fn get_tour_distance(tour:Vec<usize>, cities_list:&[City], metric:DistanceMetric) -> f32 {
+    // Takes a vec of city indicies (tour) and returns the total distance travelled under the metric
     let mut tour2:Vec<usize> = tour.clone();
     tour2.rotate_left(1);
     let distance:f32 = tour.iter()
                         .zip(tour2)
                         .map(|(from_city_idx, to_city_idx)| 
-                        calculate_distance_generic(cities_list[*from_city_idx], cities_list[to_city_idx])).sum();
+                        metric.distance(&cities_list[*from_city_idx], &cities_list[to_city_idx])).sum();
     distance
 }
 
+pub fn get_tour_distance_graph(tour:&[usize], dist_graph:&GraphMatrix) -> f32 {
+    // Takes a vec of city indicies (tour) and returns the total distance travelled
+    // using the distance matrix, so it also works for instances without coordinates
+    tour.iter()
+        .zip(tour.iter().cycle().skip(1))
+        .map(|(from_city_idx, to_city_idx)| dist_graph[(*from_city_idx, *to_city_idx)])
+        .sum()
+}
+
+pub(crate) fn applicable_local_search(local_search:&Option<Box<dyn LocalSearch>>, symmetric:bool) -> &dyn LocalSearch {
+    match local_search {
+        Some(search) if symmetric || !search.symmetric_only() => search.as_ref(),
+        // Segment reversal is not valid on directed edges, or-opt keeps every edge's direction
+        _ if !symmetric => &OrOptDirected,
+        _ => &NoLocalSearch,
+    }
+}
+
+
+pub(crate) fn ants_this_iteration(config:&AcoConfig, progress:&Progress) -> Result<usize, StopReason> {
+    // How many ants go out this iteration, or why the run stops before it
+    match config.stop_criteria().check_before_iteration(progress) {
+        Some(reason) => Err(reason),
+        None => Ok(config.stop_criteria().ants_this_iteration(progress, config.num_ants())),
+    }
+}
+
+
+pub(crate) struct Trail<'t> {
+    // pher_graph is only written when step_update is Some, choice_info follows it
+    pub pher_graph: &'t mut GraphMatrix,
+    pub choice_info: &'t mut ChoiceInfo,
+    pub step_update: Option<StepUpdate>,
+    pub q0: f32,
+}
+
+
+pub(crate) struct Ants {
+    rng: AntRng,
+    builders: Vec<TourBuilder>,
+    candidates: Option<CandidateLists>,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl Ants {
+
+    pub(crate) fn new(cities_list:&[City], dist_graph:&GraphMatrix, config:&AcoConfig) -> Result<Ants, AcoError> {
+        // The state every colony needs to build tours, shared by ACO3 and MMAS
+        Ok(Ants{rng:colony_rng(config.seed()), builders:vec![TourBuilder::new(cities_list.len()); config.num_ants()],
+                candidates:CandidateLists::from_config(cities_list, dist_graph, config), pool:config.thread_pool()?})
+    }
+
+    pub(crate) fn candidates(&self) -> Option<&CandidateLists> {
+        self.candidates.as_ref()
+    }
+
+    pub(crate) fn construct_and_improve(&mut self, num_ants:usize, trail:Trail, local_search:&dyn LocalSearch, dist_graph:&GraphMatrix) -> Vec<Vec<usize>> {
+        /* Builds num_ants tours on the trail and improves each one with local_search.
+           Every ant draws from its own stream, so the tours are the same with or without rayon */
+        let ant_rngs:Vec<AntRng> = ant_rngs(&mut self.rng, num_ants);
+        let (builders, candidates, pool) = (&mut self.builders[..num_ants], self.candidates.as_ref(), self.pool.as_deref());
+        let Trail{pher_graph, choice_info, step_update, q0} = trail;
+        let mut tours:Vec<Vec<usize>> = match step_update {
+            // Every ant has to see the trail left by the ants before it, so they cannot run in parallel
+            Some(step_update) => builders.iter_mut().zip(ant_rngs)
+                                         .map(|(builder, mut rng)| builder.build_with_step_update(pher_graph, choice_info, &step_update,
+                                                                                                  q0, candidates, &mut rng))
+                                         .collect(),
+            None => {
+                let choice_info:&ChoiceInfo = choice_info;
+                match pool {
+                    Some(pool) => pool.install(|| builders.par_iter_mut().zip(ant_rngs)
+                                                          .map(|(builder, mut rng)| builder.build(choice_info, q0, candidates, &mut rng))
+                                                          .collect()),
+                    None => builders.iter_mut().zip(ant_rngs)
+                                    .map(|(builder, mut rng)| builder.build(choice_info, q0, candidates, &mut rng))
+                                    .collect(),
+                }
+            },
+        };
+        match pool {
+            Some(pool) => pool.install(|| tours.par_iter_mut().for_each(|tour| {local_search.improve(tour, dist_graph, candidates);})),
+            None => tours.iter_mut().for_each(|tour| {local_search.improve(tour, dist_graph, candidates);}),
+        }
+        tours
+    }
+}
+
 pub struct ACO3<'a>{
     cities_list:&'a Vec<City>, 
     pher_graph: GraphMatrix, 
     dist_graph:GraphMatrix, 
-    iterations:i32, 
-    num_ants:i32, 
-    q0:f32, 
-    beta:f32,
-    rho:f32, 
-    tau:f32,
-    alpha:f32,
-    shortest_tour:Vec<usize>
+    config:AcoConfig,
+    shortest_tour:Option<Tour>,
+    symmetric:bool,
+    stop_reason:Option<StopReason>,
+    strategy:Box<dyn PheromoneStrategy>,
+    local_search:Option<Box<dyn LocalSearch>>,
+    choice_info:ChoiceInfo,
+    ants:Ants,
+    iterations_run:usize,
 }
 
 impl <'a> ACO3 <'a> {
 
-    pub fn new(cities_list:&'a Vec<City>, pher_graph: GraphMatrix, dist_graph:GraphMatrix, iterations:i32, num_ants:i32, shortest_tour:Vec<usize>) -> ACO3<'a> {
-        ACO3{cities_list, pher_graph, dist_graph, iterations, num_ants, shortest_tour, q0:0.90, beta:0.20, rho:0.1, tau:0.0005, alpha:0.1}
+    pub fn new(cities_list:&'a Vec<City>, pher_graph: GraphMatrix, dist_graph:GraphMatrix, config:AcoConfig, shortest_tour:Option<Tour>) -> Result<ACO3<'a>, AcoError> {
+        // The distance matrix may be asymmetric, dist_graph[(i, j)] is the cost of going from i to j
+        if cities_list.is_empty() {
+            return Err(AcoError::EmptyInstance);
+        }
+        check_square(&pher_graph, cities_list.len())?;
+        check_square(&dist_graph, cities_list.len())?;
+        if let Some(tour) = shortest_tour.as_ref().filter(|tour| tour.cities().len() != cities_list.len()) {
+            // A Tour is a permutation of its own cities, it may have been built for another instance
+            return Err(AcoError::NotAPermutation{message:format!("the reference tour has {} cities but the instance has {}",
+                                                                 tour.cities().len(), cities_list.len())});
+        }
+        config.validate()?;
+        let symmetric:bool = is_symmetric(&dist_graph);
+        let choice_info:ChoiceInfo = ChoiceInfo::new(&pher_graph, &dist_graph, config.beta());
+        let ants:Ants = Ants::new(cities_list, &dist_graph, &config)?;
+        Ok(ACO3{cities_list, pher_graph, dist_graph, config, shortest_tour, symmetric, stop_reason:None,
+                strategy:Box::new(AntColonySystem::default()), local_search:None, choice_info, ants, iterations_run:0})
     } 
 
-    pub fn optimize(&mut self) -> Vec<usize>{
-        let mut best_tour:Vec<usize>= Vec::new();
-        let mut best_tour_distance= std::f32::INFINITY;
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    pub fn with_strategy(mut self, strategy:Box<dyn PheromoneStrategy>) -> Self {
+        // Replaces the default Ant Colony System pheromone updates
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn dist_graph(&self) -> &GraphMatrix {
+        &self.dist_graph
+    }
+
+    pub fn with_local_search(mut self, local_search:Box<dyn LocalSearch>) -> Self {
+        /* Improves every ant's tour before the pheromone update, using the candidate lists when there are any.
+           Without one, tours of asymmetric instances get directed or-opt and symmetric ones are left alone.
+           Searches that reverse segments are replaced by directed or-opt on asymmetric instances */
+        self.local_search = Some(local_search);
+        self
+    }
+
+    pub fn local_search_name(&self) -> String {
+        self.local_search().name()
+    }
+
+    fn local_search(&self) -> &dyn LocalSearch {
+        applicable_local_search(&self.local_search, self.symmetric)
+    }
+
+    pub fn candidates(&self) -> Option<&CandidateLists> {
+        // The lists the ants were built with, for local search to reuse
+        self.ants.candidates()
+    }
+
+    pub fn strategy_name(&self) -> &'static str {
+        self.strategy.name()
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        // Why the last call to optimize stopped, None before the first call
+        self.stop_reason
+    }
 
-        for i in 0..self.iterations{
+    pub fn iterations_run(&self) -> usize {
+        // Iterations completed by the last call to optimize
+        self.iterations_run
+    }
+
+    pub fn optimize(&mut self) -> Tour {
+        // At least one iteration is run so there is always a tour to return
+        let mut best_tour:Vec<usize>= Vec::new();
+        let mut best_tour_distance= f32::INFINITY;
+        let shortest_tour_distance:Option<f32> = self.shortest_tour.as_ref().map(|tour| tour.distance(&self.dist_graph));
+        let mut progress:Progress = Progress::new();
+        self.stop_reason = Some(StopReason::Iterations);
+
+        for i in 0..self.config.iterations(){
+
+            let num_ants:usize = match ants_this_iteration(&self.config, &progress) {
+                Ok(num_ants) => num_ants,
+                Err(reason) => {
+                    println!("\nStopped at {} iteration, {}", i, reason);
+                    self.stop_reason = Some(reason);
+                    break;
+                },
+            };
 
             println!("Iteration -> {}, best_distance_found -> {:.2}", i, best_tour_distance);
 
-            if get_tour_distance(self.shortest_tour.clone(), self.cities_list).round() == best_tour_distance.round() {
+            if shortest_tour_distance.is_some_and(|distance| distance.round() == best_tour_distance.round()) {
                 println!("\nShort path found at {} iteration", i);
+                self.stop_reason = Some(StopReason::TargetReached);
                 break;
             }
 
-            let tours:Vec<Vec<usize>> = (0..self.num_ants)
-                                        .map(|_| 
-                                        build_tour(self.cities_list, &self.pher_graph, &self.dist_graph, self.q0, self.beta))
-                                        .collect();
-            for tour in tours{
-                self.pher_graph = local_pheromone_update3(self.pher_graph.clone(), tour.clone(), self.cities_list, self.rho, self.tau);
-                let tour_distance:f32 = get_tour_distance(tour.clone(), self.cities_list);
+            let context = UpdateContext{cities_list:self.cities_list, config:&self.config, symmetric:self.symmetric};
+            let trail = Trail{pher_graph:&mut self.pher_graph, choice_info:&mut self.choice_info,
+                              step_update:self.strategy.step_update(&context), q0:self.strategy.q0(&self.config)};
+            let local_search:&dyn LocalSearch = applicable_local_search(&self.local_search, self.symmetric);
+            let tours:Vec<Vec<usize>> = self.ants.construct_and_improve(num_ants, trail, local_search, &self.dist_graph);
+            let mut tour_distances:Vec<f32> = Vec::with_capacity(tours.len());
+            for tour in tours.iter(){
+                self.strategy.local_update(&mut self.pher_graph, tour, &context);
+                let tour_distance:f32 = get_tour_distance_graph(tour, &self.dist_graph);
                 if tour_distance < best_tour_distance{
-                    best_tour = tour.clone();
+                    best_tour.clone_from(tour);
                     best_tour_distance = tour_distance;
                     }
+                tour_distances.push(tour_distance);
             }
-            self.pher_graph = global_pheromone_update(self.pher_graph.clone(), best_tour.clone(), self.cities_list, self.alpha, best_tour_distance);
+            let iteration = IterationTours{iteration:i, tours:&tours, distances:&tour_distances, best_tour:&best_tour, best_distance:best_tour_distance};
+            self.strategy.global_update(&mut self.pher_graph, &iteration, &context);
+            self.choice_info.refresh(&self.pher_graph);
+            progress.record_iteration(num_ants, best_tour_distance);
         }
-        best_tour
+        self.iterations_run = progress.iterations();
+        Tour::new(best_tour, self.cities_list.len()).expect("every ant builds a permutation of the cities")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+
+    fn scattered_cities(num_cities:usize) -> Vec<City> {
+        (0..num_cities).map(|i| City{name:(i + 1) as i32, x:(i * 37 % 101) as f64, y:(i * 59 % 97) as f64}).collect()
+    }
+
+    fn seeded_tour(cities:&Vec<City>, seed:u64, parallel:bool) -> Tour {
+        let config:AcoConfig = AcoConfig::builder().iterations(5).num_ants(6).seed(seed).parallel(parallel).build().unwrap();
+        let mut colony = ACO3::new(cities, get_pheromone_graph(cities, 0.0005), get_distance_graph(cities, DistanceMetric::Euclidean), config, None).unwrap();
+        colony.optimize()
+    }
+
+    #[test]
+    fn a_seed_reproduces_the_same_tour() {
+        let cities:Vec<City> = scattered_cities(20);
+        assert_eq!(seeded_tour(&cities, 7, false), seeded_tour(&cities, 7, false));
+    }
+
+    #[test]
+    fn parallel_ants_build_the_same_tours() {
+        let cities:Vec<City> = scattered_cities(20);
+        assert_eq!(seeded_tour(&cities, 7, false), seeded_tour(&cities, 7, true));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn coordinate_distances_are_stored_once_per_pair() {
+        let cities:Vec<City> = scattered_cities(6);
+        let dist_graph:GraphMatrix = get_distance_graph(&cities, DistanceMetric::Manhattan);
+        assert!(dist_graph.is_symmetric_storage());
+        assert_eq!(dist_graph[(1, 4)], dist_graph[(4, 1)]);
+        assert_eq!(dist_graph[(1, 4)], calculate_distance_generic(&cities[1], &cities[4], DistanceMetric::Manhattan));
+    }
+
+    #[test]
+    fn candidates_fall_back_to_every_unvisited_city() {
+        // With one candidate per city most steps find it already visited and must look at every city left
+        let cities:Vec<City> = scattered_cities(20);
+        let dist_graph:GraphMatrix = get_distance_graph(&cities, DistanceMetric::Euclidean);
+        let candidates:CandidateLists = CandidateLists::nearest(&dist_graph, 1);
+        let choice_info:ChoiceInfo = ChoiceInfo::new(&get_pheromone_graph(&cities, 0.0005), &dist_graph, 2.0);
+        let mut rngs:Vec<AntRng> = ant_rngs(&mut colony_rng(Some(3)), 10);
+        for rng in rngs.iter_mut() {
+            let tour:Vec<usize> = build_tour(&cities, &choice_info, 0.5, Some(&candidates), rng);
+            assert!(Tour::new(tour, cities.len()).is_ok());
+        }
+    }
+
+    #[test]
+    fn choice_info_follows_the_pheromone_after_every_iteration() {
+        let cities:Vec<City> = scattered_cities(15);
+        let config:AcoConfig = AcoConfig::builder().iterations(3).num_ants(5).seed(2).build().unwrap();
+        let mut colony = ACO3::new(&cities, get_pheromone_graph(&cities, 0.0005), get_distance_graph(&cities, DistanceMetric::Euclidean), config, None).unwrap();
+        colony.optimize();
+        assert_eq!(colony.choice_info, ChoiceInfo::new(&colony.pher_graph, &colony.dist_graph, colony.config.beta()));
+    }
+
+    fn stopped_by(cities:&Vec<City>, config:AcoConfig) -> Option<StopReason> {
+        let mut colony = ACO3::new(cities, get_pheromone_graph(cities, 0.0005), get_distance_graph(cities, DistanceMetric::Euclidean), config, None).unwrap();
+        colony.optimize();
+        colony.stop_reason()
+    }
+
+    #[test]
+    fn the_stop_reason_is_reported() {
+        let cities:Vec<City> = scattered_cities(12);
+        let builder = || AcoConfig::builder().iterations(3).num_ants(10).seed(1);
+        assert_eq!(stopped_by(&cities, builder().build().unwrap()), Some(StopReason::Iterations));
+        assert_eq!(stopped_by(&cities, builder().iterations(100).max_evaluations(15).build().unwrap()), Some(StopReason::MaxEvaluations));
+        assert_eq!(stopped_by(&cities, builder().iterations(100).stagnation(1).build().unwrap()), Some(StopReason::Stagnation));
+        assert_eq!(stopped_by(&cities, builder().target_distance(f32::MAX).build().unwrap()), Some(StopReason::TargetReached));
+        let cancel:Arc<AtomicBool> = Arc::new(AtomicBool::new(true));
+        assert_eq!(stopped_by(&cities, builder().cancel_flag(cancel).build().unwrap()), Some(StopReason::Cancelled));
+    }
+}
+
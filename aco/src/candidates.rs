@@ -0,0 +1,123 @@
+use crate::ant3::GraphMatrix;
+use crate::city::City;
+use crate::config::AcoConfig;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateLists {
+    k: usize,
+    lists: Vec<Vec<usize>>,
+}
+
+
+fn sorted_by_distance(from_city_idx:usize, dist_graph:&GraphMatrix) -> Vec<usize> {
+    // Every other city, nearest first
+    let mut others:Vec<usize> = (0..dist_graph.size()).filter(|to_city_idx| *to_city_idx != from_city_idx).collect();
+    others.sort_by(|a, b| dist_graph[(from_city_idx, *a)].total_cmp(&dist_graph[(from_city_idx, *b)]));
+    others
+}
+
+
+fn quadrant(from_city:&City, to_city:&City) -> usize {
+    (if to_city.x >= from_city.x {0} else {1}) + (if to_city.y >= from_city.y {0} else {2})
+}
+
+
+impl CandidateLists {
+
+    pub fn nearest(dist_graph:&GraphMatrix, k:usize) -> CandidateLists {
+        // The k nearest cities of every city, measured along dist_graph[(from, to)] so directed instances work
+        let lists:Vec<Vec<usize>> = (0..dist_graph.size())
+                                    .map(|from_city_idx| sorted_by_distance(from_city_idx, dist_graph).into_iter().take(k).collect())
+                                    .collect();
+        CandidateLists{k, lists}
+    }
+
+    pub fn quadrant(cities_list:&[City], dist_graph:&GraphMatrix, k:usize) -> CandidateLists {
+        /* The k / 4 nearest cities in each quadrant around every city, topped up with the nearest
+           cities left so every list still has k entries. Clustered instances keep links between clusters
+           that a plain nearest list would miss */
+        let per_quadrant:usize = k / 4;
+        let lists:Vec<Vec<usize>> = (0..dist_graph.size()).map(|from_city_idx| {
+            let others:Vec<usize> = sorted_by_distance(from_city_idx, dist_graph);
+            let mut taken:Vec<usize> = vec![0; 4];
+            let mut list:Vec<usize> = Vec::with_capacity(k);
+            for to_city_idx in others.iter() {
+                let q:usize = quadrant(&cities_list[from_city_idx], &cities_list[*to_city_idx]);
+                if taken[q] < per_quadrant {
+                    taken[q] += 1;
+                    list.push(*to_city_idx);
+                }
+            }
+            for to_city_idx in others.iter() {
+                if list.len() >= k {
+                    break;
+                }
+                if !list.contains(to_city_idx) {
+                    list.push(*to_city_idx);
+                }
+            }
+            list.sort_by(|a, b| dist_graph[(from_city_idx, *a)].total_cmp(&dist_graph[(from_city_idx, *b)]));
+            list
+        }).collect();
+        CandidateLists{k, lists}
+    }
+
+    pub fn from_config(cities_list:&[City], dist_graph:&GraphMatrix, config:&AcoConfig) -> Option<CandidateLists> {
+        // The lists asked for by config.candidates(), None when ants consider every city
+        let k:usize = config.candidates()?;
+        if config.quadrant_candidates() {
+            Some(CandidateLists::quadrant(cities_list, dist_graph, k))
+        } else {
+            Some(CandidateLists::nearest(dist_graph, k))
+        }
+    }
+
+    // Maximum length of a list, shorter when the instance has k cities or fewer
+    pub fn k(&self) -> usize {self.k}
+
+    pub fn neighbours(&self, city_idx:usize) -> &[usize] {
+        // Candidates of city_idx, nearest first
+        &self.lists[city_idx]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::ant3::get_distance_graph;
+    use crate::metric::DistanceMetric;
+
+    use super::*;
+
+    fn grid_cities() -> Vec<City> {
+        // A 3 x 3 grid with unit spacing, city 4 in the middle
+        (0..9).map(|i| City{name:i + 1, x:(i % 3) as f64, y:(i / 3) as f64}).collect()
+    }
+
+    #[test]
+    fn nearest_lists_are_sorted_and_exclude_the_city() {
+        let cities:Vec<City> = grid_cities();
+        let dist_graph:GraphMatrix = get_distance_graph(&cities, DistanceMetric::Euclidean);
+        let candidates:CandidateLists = CandidateLists::nearest(&dist_graph, 4);
+        let mut middle:Vec<usize> = candidates.neighbours(4).to_vec();
+        middle.sort();
+        assert_eq!(middle, vec![1, 3, 5, 7]);
+        assert_eq!(candidates.neighbours(0)[..2].iter().map(|city| dist_graph[(0, *city)]).collect::<Vec<f32>>(), vec![1.0, 1.0]);
+        // Asking for more cities than there are gives every other city
+        assert_eq!(CandidateLists::nearest(&dist_graph, 20).neighbours(0).len(), 8);
+    }
+
+    #[test]
+    fn quadrant_lists_reach_every_side() {
+        // From a corner every city lies in one quadrant, so the list is topped up with the nearest cities,
+        // the middle city keeps one city from each quadrant
+        let cities:Vec<City> = grid_cities();
+        let dist_graph:GraphMatrix = get_distance_graph(&cities, DistanceMetric::Euclidean);
+        let candidates:CandidateLists = CandidateLists::quadrant(&cities, &dist_graph, 4);
+        assert_eq!(candidates.neighbours(0).len(), 4);
+        assert!(!candidates.neighbours(0).contains(&0));
+        let quadrants:Vec<usize> = candidates.neighbours(4).iter().map(|city| quadrant(&cities[4], &cities[*city])).collect();
+        assert!(quadrants.contains(&1) && quadrants.contains(&2) && quadrants.contains(&3));
+    }
+}
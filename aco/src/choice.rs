@@ -0,0 +1,88 @@
+use crate::ant3::GraphMatrix;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChoiceInfo {
+    heuristic: GraphMatrix,
+    choice: GraphMatrix,
+}
+
+
+impl ChoiceInfo {
+
+    pub fn new(pher_graph:&GraphMatrix, dist_graph:&GraphMatrix, beta:f32) -> ChoiceInfo {
+        /* eta^beta = (1 / distance)^beta is computed once per instance, the choice matrix holds
+           pheromone * eta^beta and has to be refreshed whenever the pheromone changes.
+           The pheromone exponent is fixed at 1, config.alpha() is the global decay and not an exponent here.
+           Directed cost matrices can have zero cost edges, those are clamped to avoid dividing by zero */
+        let eta_beta = |from_city_idx:usize, to_city_idx:usize|
+            if from_city_idx == to_city_idx {0.0} else {f32::powf(1.0/dist_graph[(from_city_idx, to_city_idx)].max(f32::EPSILON), beta)};
+        // eta^beta keeps the storage of the distances, the choice matrix is always full like the pheromone
+        let heuristic:GraphMatrix = if dist_graph.is_symmetric_storage() {GraphMatrix::from_fn_symmetric(dist_graph.size(), eta_beta)}
+                                    else {GraphMatrix::from_fn(dist_graph.size(), eta_beta)};
+        let choice:GraphMatrix = GraphMatrix::from_fn(dist_graph.size(), |from_city_idx, to_city_idx| heuristic[(from_city_idx, to_city_idx)]);
+        let mut choice_info = ChoiceInfo{choice, heuristic};
+        choice_info.refresh(pher_graph);
+        choice_info
+    }
+
+    pub fn refresh(&mut self, pher_graph:&GraphMatrix) {
+        // Recomputes pheromone * eta^beta for every edge, called after each pheromone update
+        let heuristic:&GraphMatrix = &self.heuristic;
+        self.choice.map(|from_city_idx, to_city_idx, _| pher_graph[(from_city_idx, to_city_idx)] * heuristic[(from_city_idx, to_city_idx)]);
+    }
+
+    pub fn refresh_edge(&mut self, pher_graph:&GraphMatrix, from_city_idx:usize, to_city_idx:usize) {
+        // Recomputes a single edge, for updates that only touch the edges an ant crossed
+        self.choice[(from_city_idx, to_city_idx)] = pher_graph[(from_city_idx, to_city_idx)] * self.heuristic[(from_city_idx, to_city_idx)];
+    }
+
+    // The eta^beta matrix
+    pub fn heuristic(&self) -> &GraphMatrix {&self.heuristic}
+
+    // Number of cities
+    pub fn size(&self) -> usize {self.choice.size()}
+
+    #[inline]
+    pub fn score(&self, from_city_idx:usize, to_city_idx:usize) -> f32 {
+        // How attractive to_city_idx is from from_city_idx, the weight used by the ants' decision rule
+        self.choice.get(from_city_idx, to_city_idx)
+    }
+
+    /// # Safety
+    /// Both indices must be less than `size()`.
+    #[inline]
+    pub(crate) unsafe fn score_unchecked(&self, from_city_idx:usize, to_city_idx:usize) -> f32 {
+        // score without the bounds check, for the construction loop which checks its indices once per step
+        self.choice.get_unchecked(from_city_idx, to_city_idx)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_follows_the_pheromone() {
+        let dist_graph:GraphMatrix = GraphMatrix::from_fn(3, |i, j| if i == j {0.0} else {(i + j) as f32});
+        let mut pher_graph:GraphMatrix = GraphMatrix::new(3, 1.0);
+        let mut choice_info:ChoiceInfo = ChoiceInfo::new(&pher_graph, &dist_graph, 2.0);
+        assert_eq!(choice_info.score(0, 2), 0.25);
+        assert!((choice_info.heuristic()[(1, 2)] - 1.0 / 9.0).abs() < 1e-6);
+        assert_eq!(choice_info.score(1, 1), 0.0);
+        pher_graph[(0, 2)] = 4.0;
+        // The choice matrix keeps the old pheromone until it is refreshed
+        assert_eq!(choice_info.score(0, 2), 0.25);
+        choice_info.refresh(&pher_graph);
+        assert_eq!(choice_info.score(0, 2), 1.0);
+        assert_eq!(choice_info.score(2, 0), 0.25);
+    }
+
+    #[test]
+    fn zero_cost_edges_stay_finite() {
+        let dist_graph:GraphMatrix = GraphMatrix::from_fn(2, |_, _| 0.0);
+        let choice_info:ChoiceInfo = ChoiceInfo::new(&GraphMatrix::new(2, 1.0), &dist_graph, 2.0);
+        assert!(choice_info.score(0, 1).is_finite() && choice_info.score(0, 1) > 0.0);
+    }
+}
@@ -1,43 +1,87 @@
-use std::fs;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 
+use crate::error::{AcoError, read_file, parse_token};
 
-#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+
+#[derive(Debug, Clone, Copy)]
 pub struct City {
     pub name: i32,
-    pub x: i32,
-    pub y: i32,
+    pub x: f64,
+    pub y: f64,
+}
+
+// Coordinates are compared by their bit patterns so City can still be used as a HashMap key
+impl PartialEq for City {
+    fn eq(&self, other:&Self) -> bool {
+        self.name == other.name && self.x.to_bits() == other.x.to_bits() && self.y.to_bits() == other.y.to_bits()
+    }
+}
+
+impl Eq for City {}
+
+impl Hash for City {
+    fn hash<H: Hasher>(&self, state:&mut H) {
+        self.name.hash(state);
+        self.x.to_bits().hash(state);
+        self.y.to_bits().hash(state);
+    }
+}
+
+fn parse_coordinate_line(line_idx:usize, line:&str) -> Result<(f64, f64), AcoError> {
+    // Takes an "x, y" line and returns a float tuple
+    // Integer coordinates such as "54, 67" parse the same way
+    let mut coordinates = line.split(',');
+    let x_str:&str = coordinates.next().unwrap_or(line).trim();
+    let y_str:&str = match coordinates.next() {
+        Some(y_str) => y_str.trim(),
+        None => return Err(AcoError::Parse{line:line_idx + 1, column:line.len() + 1,
+                                           message:"expected \"x, y\"".to_string()}),
+    };
+    if let Some(extra) = coordinates.next() {
+        let column:usize = (extra.as_ptr() as usize) - (line.as_ptr() as usize);
+        return Err(AcoError::Parse{line:line_idx + 1, column, message:"expected only two coordinates".to_string()});
+    }
+    let x:f64 = parse_token(line_idx, line, x_str, "x coordinate")?;
+    let y:f64 = parse_token(line_idx, line, y_str, "y coordinate")?;
+    Ok((x, y))
 }
 
-fn parse_coordinates(v: Vec<&str>) -> Vec<(i32, i32)> {
-    // Takes a string tuple and returns an int tuple
-    v.into_iter().map(|s| {
-        let mut coordinates = s.split(',');
-        let x = coordinates.next().unwrap().trim().parse::<i32>().unwrap();
-        let y = coordinates.next().unwrap().trim().parse::<i32>().unwrap();
-        (x, y)
-    }).collect()
+
+pub fn parse_coordinates(contents:&str) -> Result<Vec<(f64, f64)>, AcoError> {
+    // Takes the contents of a coordinates file and returns the coordinates, skipping blank lines
+    // Cities are numbered by line, so two cities may share coordinates
+    let mut coords:Vec<(f64, f64)> = Vec::new();
+    for (line_idx, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        coords.push(parse_coordinate_line(line_idx, line)?);
+    }
+    if coords.is_empty() {
+        return Err(AcoError::EmptyInstance);
+    }
+    Ok(coords)
 }
 
 
-pub fn cities_from_coordinates(file_path:&str) -> Vec<City> {
+pub fn cities_from_coordinates(file_path:&str) -> Result<Vec<City>, AcoError> {
     // Takes a file path to coordinates.txt and returns a vec of Cities
-    let coords_str:String = fs::read_to_string(file_path).expect("Cannot read file");
-    let lines: Vec<_> = coords_str.split("\r\n").collect();
-    let int_coords: Vec<(i32, i32)> = parse_coordinates(lines);
-    let mut city_vec:Vec<City> = Vec::with_capacity(int_coords.len());
-    for (i, (c1,c2)) in (1..=int_coords.len()).zip(int_coords) {
+    let coords_str:String = read_file(file_path)?;
+    let coords: Vec<(f64, f64)> = parse_coordinates(&coords_str)?;
+    let mut city_vec:Vec<City> = Vec::with_capacity(coords.len());
+    for (i, (c1,c2)) in (1..=coords.len()).zip(coords) {
         city_vec.push(City{name:(i as i32), x:c1, y:c2});
     }
-    return city_vec;
+    Ok(city_vec)
 }
 
 
-fn get_city<'a>(city_name: i32, cities_list: &'a Vec<City>) -> &'a City {
+fn get_city(city_name: i32, cities_list: &[City]) -> Option<&City> {
     // Takes a city name and returns a City object
     cities_list
         .iter()
         .find(|city| city.name == city_name)
-        .expect("City not found")
 }
 
 
@@ -59,19 +103,59 @@ fn get_city<'a>(city_name: i32, cities_list: &'a Vec<City>) -> &'a City {
 // }  
 
 
-pub fn get_shortest_path<'a>(file_path:&str, cities_list:&'a Vec<City>) -> Vec<&'a City> {
+pub fn get_shortest_path<'a>(file_path:&str, cities_list:&'a [City]) -> Result<Vec<&'a City>, AcoError> {
     // Takes a file with city numbers and returns a vector of Cities
-    let short_path_nums:String = fs::read_to_string(file_path).expect("Cannot read file");    
-    let lines: Vec<&str> = short_path_nums.split(" ").collect();
-    let vec2: Vec<i32> = lines
-                        .into_iter()
-                        .map(|s| s.trim()
-                        .parse::<i32>()
-                        .unwrap())
-                        .collect();
-    let mut short_path: Vec<&City> = vec2
-                                     .iter()
-                                     .map(|city_name| get_city(*city_name, &cities_list))
-                                     .collect();
-    short_path
-}  
\ No newline at end of file
+    // Every city has to appear exactly once
+    let short_path_nums:String = read_file(file_path)?;
+    let mut short_path:Vec<&City> = Vec::with_capacity(cities_list.len());
+    let mut seen:HashSet<i32> = HashSet::new();
+    for (line_idx, line) in short_path_nums.lines().enumerate() {
+        for token in line.split_whitespace() {
+            let city_name:usize = parse_token(line_idx, line, token, "city number")?;
+            let city:&City = i32::try_from(city_name).ok()
+                             .and_then(|city_name| get_city(city_name, cities_list))
+                             .ok_or(AcoError::UnknownCity{city:city_name, num_cities:cities_list.len()})?;
+            if !seen.insert(city.name) {
+                return Err(AcoError::NotAPermutation{message:format!("city {} is visited more than once", city.name)});
+            }
+            short_path.push(city);
+        }
+    }
+    if short_path.len() != cities_list.len() {
+        return Err(AcoError::NotAPermutation{
+            message:format!("tour has {} cities but the instance has {}", short_path.len(), cities_list.len())});
+    }
+    Ok(short_path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coordinates_skip_blank_lines() {
+        assert_eq!(parse_coordinates("1, 2\n\n3.5, -4\n").unwrap(), vec![(1.0, 2.0), (3.5, -4.0)]);
+    }
+
+    #[test]
+    fn cities_may_share_coordinates() {
+        // Generated integer instances often place two cities on the same point
+        assert_eq!(parse_coordinates("1, 2\n1, 2\n").unwrap(), vec![(1.0, 2.0), (1.0, 2.0)]);
+    }
+
+    #[test]
+    fn parse_errors_report_line_and_column() {
+        // Blank lines still count towards the line number
+        let error:AcoError = parse_coordinates("1, 2\n\n3, y\n").unwrap_err();
+        assert!(matches!(error, AcoError::Parse{line:3, column:4, ..}));
+        assert_eq!(error.to_string(), "line 3, column 4: cannot parse y coordinate from 'y'");
+        assert!(matches!(parse_coordinates("1 2"), Err(AcoError::Parse{line:1, column:4, ..})));
+        assert!(matches!(parse_coordinates("1, 2, 3"), Err(AcoError::Parse{line:1, column:5, ..})));
+    }
+
+    #[test]
+    fn an_empty_file_is_rejected() {
+        assert!(matches!(parse_coordinates("\n \n"), Err(AcoError::EmptyInstance)));
+    }
+}
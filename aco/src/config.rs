@@ -0,0 +1,293 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::error::AcoError;
+use crate::stop::StopCriteria;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    NotANumber(&'static str),
+    OutOfRange{name:&'static str, value:f32, min:f32, max:f32},
+    NotPositive{name:&'static str, value:f32},
+    TooSmall{name:&'static str, value:usize, min:usize},
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NotANumber(name) => write!(f, "{} must be a number", name),
+            ConfigError::OutOfRange{name, value, min, max} =>
+                write!(f, "{} must be between {} and {}, got {}", name, min, max, value),
+            ConfigError::NotPositive{name, value} => write!(f, "{} must be greater than 0, got {}", name, value),
+            ConfigError::TooSmall{name, value, min} => write!(f, "{} must be at least {}, got {}", name, min, value),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcoConfig {
+    iterations: usize,
+    num_ants: usize,
+    q0: f32,
+    alpha: f32,
+    beta: f32,
+    rho: f32,
+    tau: f32,
+    seed: Option<u64>,
+    parallel: bool,
+    threads: Option<usize>,
+    candidates: Option<usize>,
+    quadrant_candidates: bool,
+    stop: StopCriteria,
+}
+
+impl Default for AcoConfig {
+    fn default() -> Self {
+        AcoConfig{iterations:100, num_ants:10, q0:0.90, alpha:0.1, beta:0.20, rho:0.1, tau:0.0005, seed:None, parallel:false, threads:None,
+                  candidates:None, quadrant_candidates:false, stop:StopCriteria::default()}
+    }
+}
+
+
+pub(crate) fn check_range(name:&'static str, value:f32, min:f32, max:f32) -> Result<(), ConfigError> {
+    // Rejects NaN and values outside [min, max]
+    if value.is_nan() {
+        return Err(ConfigError::NotANumber(name));
+    }
+    if value < min || value > max {
+        return Err(ConfigError::OutOfRange{name, value, min, max});
+    }
+    Ok(())
+}
+
+
+pub(crate) fn check_positive(name:&'static str, value:f32, max:f32) -> Result<(), ConfigError> {
+    // Rejects NaN and values outside (0, max]
+    if value <= 0.0 {
+        return Err(ConfigError::NotPositive{name, value});
+    }
+    check_range(name, value, 0.0, max)
+}
+
+
+pub(crate) fn check_min(name:&'static str, value:usize, min:usize) -> Result<(), ConfigError> {
+    // Rejects counts below min
+    if value < min {
+        return Err(ConfigError::TooSmall{name, value, min});
+    }
+    Ok(())
+}
+
+
+impl AcoConfig {
+
+    pub fn builder() -> AcoConfigBuilder {
+        AcoConfigBuilder::default()
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // Checks every parameter is a number inside its valid range
+        check_min("num_ants", self.num_ants, 1)?;
+        check_min("iterations", self.iterations, 1)?;
+        check_range("q0", self.q0, 0.0, 1.0)?;
+        check_range("alpha", self.alpha, 0.0, 1.0)?;
+        check_range("beta", self.beta, 0.0, f32::MAX)?;
+        check_range("rho", self.rho, 0.0, 1.0)?;
+        check_positive("tau", self.tau, f32::MAX)?;
+        if let Some(k) = self.candidates {
+            check_min("candidates", k, 1)?;
+        }
+        if let Some(threads) = self.threads {
+            check_min("threads", threads, 1)?;
+        }
+        if let Some(max_evaluations) = self.stop.max_evaluations {
+            check_min("max_evaluations", max_evaluations, 1)?;
+        }
+        if let Some(stagnation) = self.stop.stagnation {
+            check_min("stagnation", stagnation, 1)?;
+        }
+        if let Some(target) = self.stop.target_distance {
+            check_range("target_distance", target, 0.0, f32::MAX)?;
+        }
+        Ok(())
+    }
+
+    // Number of iterations the colony runs for
+    pub fn iterations(&self) -> usize {self.iterations}
+    // Number of ants building a tour every iteration
+    pub fn num_ants(&self) -> usize {self.num_ants}
+    // Probability of taking the best scored city instead of sampling one
+    pub fn q0(&self) -> f32 {self.q0}
+    // Pheromone decay of the global update
+    pub fn alpha(&self) -> f32 {self.alpha}
+    // Weight of the distance heuristic when scoring a city
+    pub fn beta(&self) -> f32 {self.beta}
+    // Pheromone decay of the local update
+    pub fn rho(&self) -> f32 {self.rho}
+    // Initial pheromone, also deposited by the local update
+    pub fn tau(&self) -> f32 {self.tau}
+    // Seed for the random number generator, None seeds from the OS
+    pub fn seed(&self) -> Option<u64> {self.seed}
+    // Build the ants' tours on the rayon thread pool
+    pub fn parallel(&self) -> bool {self.parallel}
+    // Worker threads of the parallel pool, None uses one per core
+    pub fn threads(&self) -> Option<usize> {self.threads}
+    // Length of every city's candidate list, None lets the ants consider every city
+    pub fn candidates(&self) -> Option<usize> {self.candidates}
+    // Spread the candidates over the four quadrants around a city instead of taking the nearest
+    pub fn quadrant_candidates(&self) -> bool {self.quadrant_candidates}
+    // When to stop before running every iteration, checked at the start of each one
+    pub fn stop_criteria(&self) -> &StopCriteria {&self.stop}
+
+    pub(crate) fn thread_pool(&self) -> Result<Option<Arc<ThreadPool>>, AcoError> {
+        // Each colony gets its own bounded pool, so colonies running side by side do not share rayon's global one.
+        // None when the colony runs sequentially
+        if !self.parallel {
+            return Ok(None);
+        }
+        let pool:ThreadPool = ThreadPoolBuilder::new().num_threads(self.threads.unwrap_or(0)).build().map_err(AcoError::ThreadPool)?;
+        Ok(Some(Arc::new(pool)))
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct AcoConfigBuilder {
+    config: AcoConfig,
+}
+
+impl AcoConfigBuilder {
+
+    pub fn iterations(mut self, iterations:usize) -> Self {
+        self.config.iterations = iterations;
+        self
+    }
+
+    pub fn num_ants(mut self, num_ants:usize) -> Self {
+        self.config.num_ants = num_ants;
+        self
+    }
+
+    pub fn q0(mut self, q0:f32) -> Self {
+        self.config.q0 = q0;
+        self
+    }
+
+    pub fn alpha(mut self, alpha:f32) -> Self {
+        self.config.alpha = alpha;
+        self
+    }
+
+    pub fn beta(mut self, beta:f32) -> Self {
+        self.config.beta = beta;
+        self
+    }
+
+    pub fn rho(mut self, rho:f32) -> Self {
+        self.config.rho = rho;
+        self
+    }
+
+    pub fn tau(mut self, tau:f32) -> Self {
+        self.config.tau = tau;
+        self
+    }
+
+    pub fn seed(mut self, seed:u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn parallel(mut self, parallel:bool) -> Self {
+        self.config.parallel = parallel;
+        self
+    }
+
+    pub fn threads(mut self, threads:usize) -> Self {
+        self.config.threads = Some(threads);
+        self
+    }
+
+    pub fn candidates(mut self, k:usize) -> Self {
+        self.config.candidates = Some(k);
+        self
+    }
+
+    pub fn quadrant_candidates(mut self, quadrant_candidates:bool) -> Self {
+        self.config.quadrant_candidates = quadrant_candidates;
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit:Duration) -> Self {
+        self.config.stop.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn max_evaluations(mut self, max_evaluations:usize) -> Self {
+        self.config.stop.max_evaluations = Some(max_evaluations);
+        self
+    }
+
+    pub fn stagnation(mut self, iterations:usize) -> Self {
+        self.config.stop.stagnation = Some(iterations);
+        self
+    }
+
+    pub fn target_distance(mut self, target_distance:f32) -> Self {
+        self.config.stop.target_distance = Some(target_distance);
+        self
+    }
+
+    pub fn cancel_flag(mut self, cancel:Arc<AtomicBool>) -> Self {
+        self.config.stop.cancel = Some(cancel);
+        self
+    }
+
+    pub fn build(self) -> Result<AcoConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        let config:AcoConfig = AcoConfig::builder().build().unwrap();
+        assert_eq!(config, AcoConfig::default());
+    }
+
+    #[test]
+    fn builder_sets_every_parameter() {
+        let config:AcoConfig = AcoConfig::builder().iterations(5).num_ants(3).q0(0.5).alpha(0.2).beta(2.0).rho(0.3).tau(0.01).build().unwrap();
+        assert_eq!((config.iterations(), config.num_ants()), (5, 3));
+        assert_eq!((config.q0(), config.alpha(), config.beta(), config.rho(), config.tau()), (0.5, 0.2, 2.0, 0.3, 0.01));
+    }
+
+    #[test]
+    fn nan_is_rejected() {
+        assert_eq!(AcoConfig::builder().beta(f32::NAN).build(), Err(ConfigError::NotANumber("beta")));
+    }
+
+    #[test]
+    fn values_outside_their_range_are_rejected() {
+        assert_eq!(AcoConfig::builder().q0(1.5).build(), Err(ConfigError::OutOfRange{name:"q0", value:1.5, min:0.0, max:1.0}));
+        assert!(matches!(AcoConfig::builder().rho(-0.1).build(), Err(ConfigError::OutOfRange{name:"rho", ..})));
+        assert_eq!(AcoConfig::builder().tau(0.0).build(), Err(ConfigError::NotPositive{name:"tau", value:0.0}));
+        assert_eq!(AcoConfig::builder().candidates(0).build(), Err(ConfigError::TooSmall{name:"candidates", value:0, min:1}));
+        assert_eq!(AcoConfig::builder().iterations(0).build(), Err(ConfigError::TooSmall{name:"iterations", value:0, min:1}));
+        assert_eq!(AcoConfig::builder().num_ants(0).build(), Err(ConfigError::TooSmall{name:"num_ants", value:0, min:1}));
+        assert_eq!(AcoConfig::builder().stagnation(0).build(), Err(ConfigError::TooSmall{name:"stagnation", value:0, min:1}));
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use rayon::ThreadPoolBuildError;
+
+use crate::config::ConfigError;
+
+
+#[derive(Debug)]
+pub enum AcoError {
+    Io{path:String, source:io::Error},
+    // line and column start from 1
+    Parse{line:usize, column:usize, message:String},
+    DuplicateCity{city:usize, line:usize},
+    EmptyInstance,
+    InvalidInstance{message:String},
+    UnknownCity{city:usize, num_cities:usize},
+    NotAPermutation{message:String},
+    DimensionMismatch{expected:usize, found:usize},
+    NoTour,
+    ThreadPool(ThreadPoolBuildError),
+    Config(ConfigError),
+}
+
+impl fmt::Display for AcoError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AcoError::Io{path, source} => write!(f, "cannot read {}: {}", path, source),
+            AcoError::Parse{line, column, message} => write!(f, "line {}, column {}: {}", line, column, message),
+            AcoError::DuplicateCity{city, line} => write!(f, "line {}: city {} is defined more than once", line, city),
+            AcoError::EmptyInstance => write!(f, "the instance has no cities"),
+            AcoError::InvalidInstance{message} => write!(f, "invalid instance: {}", message),
+            AcoError::UnknownCity{city, num_cities} =>
+                write!(f, "tour references city {} but the instance has {} cities", city, num_cities),
+            AcoError::NotAPermutation{message} => write!(f, "tour is not a permutation of the cities: {}", message),
+            AcoError::DimensionMismatch{expected, found} =>
+                write!(f, "expected a {}x{} matrix, found {} rows or columns", expected, expected, found),
+            AcoError::NoTour => write!(f, "no tour was built, allow more iterations or time"),
+            AcoError::ThreadPool(error) => write!(f, "cannot start the thread pool: {}", error),
+            AcoError::Config(error) => write!(f, "invalid config: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AcoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AcoError::Io{source, ..} => Some(source),
+            AcoError::ThreadPool(error) => Some(error),
+            AcoError::Config(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for AcoError {
+    fn from(error:ConfigError) -> Self {
+        AcoError::Config(error)
+    }
+}
+
+
+pub fn read_file(file_path:&str) -> Result<String, AcoError> {
+    // Reads a whole file, keeping the path in the error
+    std::fs::read_to_string(file_path).map_err(|source| AcoError::Io{path:file_path.to_string(), source})
+}
+
+
+pub(crate) fn parse_token<T: FromStr>(line_idx:usize, line:&str, token:&str, what:&str) -> Result<T, AcoError> {
+    // Parses a token taken from line, reporting the 1-based line and column on failure
+    // token must be a slice of line so its column can be worked out
+    token.parse::<T>().map_err(|_| {
+        AcoError::Parse{line:line_idx + 1, column:token_column(line, token), message:format!("cannot parse {} from '{}'", what, token)}
+    })
+}
+
+
+pub(crate) fn token_column(line:&str, token:&str) -> usize {
+    // 1-based column of token, which must be a slice of line
+    (token.as_ptr() as usize).saturating_sub(line.as_ptr() as usize) + 1
+}
@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use crate::city::City;
+use crate::metric::DistanceMetric;
 pub type Graph = HashMap<City, HashMap<City, f32>>;
 use std::borrow::Borrow;
 
@@ -12,7 +13,7 @@ pub fn get_fully_connected_cities(cities_list:&Vec<City>) -> Vec<(&City, &City)>
     for city1 in cities_list {
         for city2 in cities_list {
             if city1.name != city2.name {
-                city_combinations.push((&city1, &city2))
+                city_combinations.push((city1, city2))
             }
         }
     }
@@ -25,37 +26,50 @@ pub fn create_pheromone_graph(cities_list:&Vec<City>, initial_pheromone:f32) ->
     let mut city_graph = Graph::new();
     let city_tuples = get_fully_connected_cities(cities_list);
     for (from_city, to_city) in city_tuples {
-        city_graph.entry(*from_city).or_insert(HashMap::new())
+        city_graph.entry(*from_city).or_default()
         .entry(*to_city).or_insert(initial_pheromone);
     }
-    let graph = Arc::new(Mutex::new(city_graph));
-    graph
+    Arc::new(Mutex::new(city_graph))
 }
 
 
-pub fn calculate_distance(city1:&City, city2:&City) -> f32 { 
-    // Returns the distance between 2 cities
-    (((city1.x - city2.x) as f32).powf(2.0) + 
-     ((city1.y - city2.y) as f32).powf(2.0)).sqrt()
+pub fn calculate_distance(city1:&City, city2:&City, metric:DistanceMetric) -> f32 { 
+    // Returns the distance between 2 cities under the given metric
+    metric.distance(city1, city2)
 }
 
 
-pub fn create_distance_graph(cities_list:&Vec<City>) -> Graph {
+pub fn create_distance_graph(cities_list:&Vec<City>, metric:DistanceMetric) -> Graph {
+    // Creates a distance graph between all connected cities using the given metric
     let mut graph = Graph::new();
     let city_tuples = get_fully_connected_cities(cities_list);
     for (from_city, to_city) in city_tuples {
         // add_nodes_distance(cities.0, cities.1, &mut graph)
-        let distance:f32 = calculate_distance(from_city, to_city);
-        graph.entry(*from_city).or_insert(HashMap::new())
+        let distance:f32 = metric.distance(from_city, to_city);
+        graph.entry(*from_city).or_default()
         .entry(*to_city).or_insert(distance);
     }
     graph
 }
 
-pub fn get_tour_tuples(tour:&Vec<City>) -> Vec<(City, City)> {
+pub fn create_heuristic_graph(distance_graph:&Graph, beta:f32) -> Graph {
+    // Takes the distance graph and returns (1 / distance)^beta for every pair of cities
+    // It does not change during a run so ants look it up instead of calling powf
+    // Cities can share coordinates, those zero distances are clamped to avoid dividing by zero
+    distance_graph.iter()
+                  .map(|(from_city, to_cities)| (*from_city, to_cities.iter()
+                       .map(|(to_city, distance)| (*to_city, f32::powf(1.0/distance.max(f32::EPSILON), beta)))
+                       .collect()))
+                  .collect()
+}
+
+pub fn get_tour_tuples(tour:&[City]) -> Vec<(City, City)> {
     // Takes a tour of city names and returns a vec of tuples   
-    // and connects the last and first cities in tour
-    let mut tour2 = tour.clone();
+    // and connects the last and first cities in tour, a tour of one city has no edges
+    if tour.len() < 2 {
+        return Vec::new();
+    }
+    let mut tour2 = tour.to_vec();
     tour2.rotate_left(1);
     let tour_city_tuples:Vec<(City, City)> = tour.iter()
                                                .zip(tour2.iter())
@@ -77,6 +91,10 @@ pub fn get_tour_tuples_generic<T>(tour:Vec<T>) -> Vec<(T,T)>
 where 
     T: Borrow<City> + Copy,
 {
+    // A tour of one city has no edges, not one from the city to itself
+    if tour.len() < 2 {
+        return Vec::new();
+    }
     let mut tour2 = tour.clone();
     tour2.rotate_left(1);
     let tour_tuples: Vec<_> = tour.iter()
@@ -87,26 +105,64 @@ where
 }
 
 
-pub fn calculate_distance_generic<T>(city1:T, city2:T) -> f32 
+pub fn calculate_distance_generic<T>(city1:T, city2:T, metric:DistanceMetric) -> f32 
 where 
     T: Borrow<City>,
 { 
-    let city1:&City = city1.borrow();
-    let city2:&City = city2.borrow();
-    (((city1.x - city2.x) as f32).powf(2.0) + ((city1.y - city2.y) as f32).powf(2.0)).sqrt()
+    metric.distance(city1.borrow(), city2.borrow())
 }
 
 
-pub fn get_tour_length_generic<T>(tour:Vec<T>) -> f32 
+pub fn get_tour_length_generic<T>(tour:Vec<T>, metric:DistanceMetric) -> f32 
 where 
     T: Borrow<City> + Copy,
 {
     let tour_tuples = get_tour_tuples_generic(tour);
     tour_tuples.iter()
                .map(|(city1, city2)| 
-               calculate_distance_generic(city1.borrow(), city2.borrow()))
+               calculate_distance_generic(city1.borrow(), city2.borrow(), metric))
                .sum()
 }
 
 
+pub fn get_tour_length_graph<T>(tour:&[T], distance_graph:&Graph) -> f32 
+where 
+    T: Borrow<City> + Copy,
+{
+    // Looks every edge up in distance_graph, so the length uses whatever metric the graph was built with
+    get_tour_tuples_generic(tour.to_vec()).iter()
+                                          .map(|(city1, city2)| distance_graph[city1.borrow()][city2.borrow()])
+                                          .sum()
+}
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tour_lengths_use_the_graph_metric() {
+        let cities:Vec<City> = vec![City{name:1, x:0.0, y:0.0}, City{name:2, x:3.0, y:4.0}, City{name:3, x:3.0, y:0.0}];
+        let distance_graph:Graph = create_distance_graph(&cities, DistanceMetric::Manhattan);
+        assert_eq!(get_tour_length_graph(&cities, &distance_graph), 14.0);
+        assert_eq!(get_tour_length_generic(cities.iter().collect(), DistanceMetric::Manhattan), 14.0);
+        assert_eq!(get_tour_length_generic(cities.iter().collect(), DistanceMetric::Euclidean), 12.0);
+    }
+
+    #[test]
+    fn a_single_city_tour_has_no_edges() {
+        let cities:Vec<City> = vec![City{name:1, x:2.0, y:5.0}];
+        assert!(get_tour_tuples(&cities).is_empty());
+        assert!(get_tour_tuples_generic(cities.iter().collect::<Vec<&City>>()).is_empty());
+        assert_eq!(get_tour_length_graph(&cities, &create_distance_graph(&cities, DistanceMetric::Euclidean)), 0.0);
+    }
+
+    #[test]
+    fn co_located_cities_get_a_finite_heuristic() {
+        let cities:Vec<City> = vec![City{name:1, x:1.0, y:1.0}, City{name:2, x:1.0, y:1.0}, City{name:3, x:4.0, y:5.0}];
+        let heuristic_graph:Graph = create_heuristic_graph(&create_distance_graph(&cities, DistanceMetric::Euclidean), 2.0);
+        assert!(heuristic_graph[&cities[0]][&cities[1]].is_finite());
+        assert!((heuristic_graph[&cities[0]][&cities[2]] - 0.04).abs() < 1e-6);
+    }
+}
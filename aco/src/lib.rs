@@ -0,0 +1,21 @@
+pub mod city;
+pub mod config;
+pub mod stop;
+pub mod error;
+pub mod rng;
+pub mod matrix;
+pub mod graph;
+pub mod candidates;
+pub mod choice;
+pub mod ant;
+pub mod ant2;
+pub mod aco;
+pub mod metric;
+pub mod ant3;
+pub mod mmas;
+pub mod strategy;
+pub mod ant_colony;
+pub mod tsplib;
+pub mod tour;
+pub mod local_search;
+pub mod solver;
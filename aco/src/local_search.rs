@@ -0,0 +1,730 @@
+use std::collections::VecDeque;
+
+use crate::ant3::GraphMatrix;
+use crate::candidates::CandidateLists;
+
+
+// Longest segment or-opt moves
+const MAX_SEGMENT_LEN:usize = 3;
+
+
+fn relocate_segment(tour:&mut [usize], positions:&mut [usize], start:usize, seg_len:usize, insert_after:usize, reverse:bool) {
+    /* Moves the seg_len cities from position start so they follow the city at position insert_after, reversing
+       the segment if asked. Only the cities between the segment and its new place move, taken from whichever
+       side of the tour is shorter */
+    let n:usize = tour.len();
+    let mut segment:[usize; MAX_SEGMENT_LEN] = [0; MAX_SEGMENT_LEN];
+    for (offset, city_idx) in segment[..seg_len].iter_mut().enumerate() {
+        *city_idx = tour[(start + offset) % n];
+    }
+    if reverse {
+        segment[..seg_len].reverse();
+    }
+    // Cities from the one after the segment up to the insertion point, the rest lie before the segment
+    let ahead:usize = (insert_after + 2 * n - start - seg_len) % n + 1;
+    let behind:usize = n - seg_len - ahead;
+    let seg_pos:usize = if ahead <= behind {
+        for offset in 0..ahead {
+            let city_idx:usize = tour[(start + seg_len + offset) % n];
+            tour[(start + offset) % n] = city_idx;
+            positions[city_idx] = (start + offset) % n;
+        }
+        (start + ahead) % n
+    } else {
+        for offset in 1..=behind {
+            let city_idx:usize = tour[(start + n - offset) % n];
+            tour[(start + seg_len + n - offset) % n] = city_idx;
+            positions[city_idx] = (start + seg_len + n - offset) % n;
+        }
+        (start + n - behind) % n
+    };
+    for (offset, city_idx) in segment[..seg_len].iter().enumerate() {
+        tour[(seg_pos + offset) % n] = *city_idx;
+        positions[*city_idx] = (seg_pos + offset) % n;
+    }
+}
+
+
+fn relocate_segments(tour:&mut [usize], dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>, max_seg_len:usize, allow_reverse:bool) -> bool {
+    /* Relocates segments of 1 to max_seg_len cities, also trying each segment reversed when allow_reverse is set.
+       Segments start at a city whose don't-look bit is off and the first improving move is applied.
+       With candidate lists a segment is only tried next to the candidates of its end cities, on either
+       side of them. Otherwise it is tried between every pair of consecutive cities and every city is queued
+       again after a round that moved something, so the result is a true or-opt local optimum.
+       Returns true if the tour was improved */
+    let n:usize = tour.len();
+    let mut improved_any:bool = false;
+    if n < 5 {
+        return improved_any;
+    }
+    let max_seg_len:usize = max_seg_len.min(MAX_SEGMENT_LEN);
+    let mut positions:Vec<usize> = vec![0; n];
+    for (pos, city_idx) in tour.iter().enumerate() {
+        positions[*city_idx] = pos;
+    }
+    let mut queue:VecDeque<usize> = tour.iter().copied().collect();
+    let mut queued:Vec<bool> = vec![true; n];
+    // Positions of the cities the segment may be inserted after
+    let mut insert_positions:Vec<usize> = Vec::with_capacity(n);
+
+    let mut round_improved:bool = false;
+    loop {
+        let seg_first:usize = match queue.pop_front() {
+            Some(city_idx) => city_idx,
+            None if candidates.is_none() && round_improved => {
+                round_improved = false;
+                queue.extend(tour.iter().copied());
+                queued.fill(true);
+                continue;
+            },
+            None => break,
+        };
+        queued[seg_first] = false;
+        // (segment length, insertion position, reversed, cities whose edges changed)
+        let mut best_move:Option<(usize, usize, bool, [usize; 6])> = None;
+        let start:usize = positions[seg_first];
+        for seg_len in 1..=max_seg_len {
+            let prev:usize = tour[(start + n - 1) % n];
+            let seg_last:usize = tour[(start + seg_len - 1) % n];
+            let next:usize = tour[(start + seg_len) % n];
+            let remove_gain:f32 = dist_graph[(prev, seg_first)] + dist_graph[(seg_last, next)] - dist_graph[(prev, next)];
+            insert_positions.clear();
+            match candidates {
+                Some(candidates) => {
+                    for city_idx in candidates.neighbours(seg_first).iter().chain(candidates.neighbours(seg_last)) {
+                        insert_positions.extend([positions[*city_idx], (positions[*city_idx] + n - 1) % n]);
+                    }
+                },
+                None => insert_positions.extend(0..n),
+            }
+            for pos in insert_positions.iter().copied() {
+                // Neither end of the edge (a, b) may be in the segment
+                if (pos + n - start) % n < seg_len || (pos + 1 + n - start) % n < seg_len {
+                    continue;
+                }
+                let (a, b) = (tour[pos], tour[(pos + 1) % n]);
+                let insert_cost:f32 = dist_graph[(a, seg_first)] + dist_graph[(seg_last, b)] - dist_graph[(a, b)];
+                // A single city reversed is the same move
+                let reversed_cost:f32 = if allow_reverse && seg_len > 1 {
+                    dist_graph[(a, seg_last)] + dist_graph[(seg_first, b)] - dist_graph[(a, b)]
+                } else {
+                    f32::INFINITY
+                };
+                if insert_cost.min(reversed_cost) - remove_gain < -1e-4 {
+                    best_move = Some((seg_len, pos, reversed_cost < insert_cost, [prev, next, a, b, seg_first, seg_last]));
+                    break;
+                }
+            }
+            if best_move.is_some() {
+                break;
+            }
+        }
+        if let Some((seg_len, pos, reverse, touched)) = best_move {
+            relocate_segment(tour, &mut positions, start, seg_len, pos, reverse);
+            improved_any = true;
+            round_improved = true;
+            for city_idx in touched {
+                if !queued[city_idx] {
+                    queued[city_idx] = true;
+                    queue.push_back(city_idx);
+                }
+            }
+        }
+    }
+    improved_any
+}
+
+
+pub fn or_opt_directed(tour:&mut [usize], dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+    // Or-opt that relocates segments of 1 to 3 cities without reversing them,
+    // so every edge keeps its direction and the move is valid for asymmetric instances
+    relocate_segments(tour, dist_graph, candidates, MAX_SEGMENT_LEN, false)
+}
+
+
+pub fn or_opt(tour:&mut [usize], dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+    // Or-opt that relocates segments of 1 to 3 cities in either orientation, for symmetric instances
+    relocate_segments(tour, dist_graph, candidates, MAX_SEGMENT_LEN, true)
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Improvement {
+    // Apply the first improving move found around a city
+    First,
+    // Apply the best improving move around a city
+    Best,
+}
+
+
+fn reverse_segment(tour:&mut [usize], positions:&mut [usize], from_pos:usize, to_pos:usize) {
+    // Reverses the cyclic run of the tour from from_pos to to_pos inclusive,
+    // or the rest of the tour when that is shorter as both give the same cycle
+    let n:usize = tour.len();
+    let len:usize = (to_pos + n - from_pos) % n + 1;
+    let (mut i, mut j, len) = if 2 * len > n {
+        ((to_pos + 1) % n, (from_pos + n - 1) % n, n - len)
+    } else {
+        (from_pos, to_pos, len)
+    };
+    for _ in 0..len / 2 {
+        tour.swap(i, j);
+        positions[tour[i]] = i;
+        positions[tour[j]] = j;
+        i = (i + 1) % n;
+        j = (j + n - 1) % n;
+    }
+}
+
+
+pub fn two_opt(tour:&mut [usize], dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>, improvement:Improvement) -> bool {
+    /* 2-opt with O(1) delta evaluation and don't-look bits, run until no move improves the tour.
+       With candidate lists only moves adding an edge to one of a city's candidates are tried,
+       otherwise every city is tried and the result is a true 2-opt local optimum.
+       Reverses segments, so it is only valid for symmetric instances.
+       Returns true if the tour was improved */
+    let n:usize = tour.len();
+    let mut improved_any:bool = false;
+    if n < 4 {
+        return improved_any;
+    }
+    let all_cities:Vec<usize> = if candidates.is_none() {(0..n).collect()} else {Vec::new()};
+    let mut positions:Vec<usize> = vec![0; n];
+    for (pos, city_idx) in tour.iter().enumerate() {
+        positions[*city_idx] = pos;
+    }
+    // A city is queued while its don't-look bit is off
+    let mut queue:VecDeque<usize> = tour.iter().copied().collect();
+    let mut queued:Vec<bool> = vec![true; n];
+
+    while let Some(a) = queue.pop_front() {
+        queued[a] = false;
+        let neighbours:&[usize] = match candidates {
+            Some(candidates) => candidates.neighbours(a),
+            None => &all_cities,
+        };
+        // (t1, t2, t3, t4, delta): remove (t1, t2) and (t3, t4), add (t1, t3) and (t2, t4)
+        let mut best_move:Option<(usize, usize, usize, usize, f32)> = None;
+        'directions: for forward in [true, false] {
+            let pos_a:usize = positions[a];
+            let b:usize = if forward {tour[(pos_a + 1) % n]} else {tour[(pos_a + n - 1) % n]};
+            let d_ab:f32 = dist_graph[(a, b)];
+            for c in neighbours.iter().copied() {
+                let d_ac:f32 = dist_graph[(a, c)];
+                if candidates.is_some() && d_ac >= d_ab {
+                    // Candidates are sorted, no later one can give an improving move
+                    break;
+                }
+                let pos_c:usize = positions[c];
+                let d:usize = if forward {tour[(pos_c + 1) % n]} else {tour[(pos_c + n - 1) % n]};
+                if c == a || c == b || d == a {
+                    continue;
+                }
+                let delta:f32 = d_ac + dist_graph[(b, d)] - d_ab - dist_graph[(c, d)];
+                if delta < -1e-4 && best_move.is_none_or(|(.., best_delta)| delta < best_delta) {
+                    best_move = Some(if forward {(a, b, c, d, delta)} else {(b, a, d, c, delta)});
+                    if improvement == Improvement::First {
+                        break 'directions;
+                    }
+                }
+            }
+        }
+        if let Some((t1, t2, t3, t4, _)) = best_move {
+            let (from_pos, to_pos) = (positions[t2], positions[t3]);
+            reverse_segment(tour, &mut positions, from_pos, to_pos);
+            improved_any = true;
+            for city_idx in [t1, t2, t3, t4] {
+                if !queued[city_idx] {
+                    queued[city_idx] = true;
+                    queue.push_back(city_idx);
+                }
+            }
+        }
+    }
+    improved_any
+}
+
+
+fn three_opt_move(tour:&[usize], dist_graph:&GraphMatrix, i:usize, j:usize, k:usize) -> Option<(usize, f32)> {
+    /* Removes the edges after positions i, i + j and i + k (j and k are offsets from i) and returns the
+       best of the seven ways to reconnect the two segments S1 = (b..c) and S2 = (d..e) with its gain.
+       Cases 0 to 2 are 2-opt moves, 3 is the pure exchange S2 S1 and 4 to 6 reverse a segment as well */
+    let n:usize = tour.len();
+    let (a, b) = (tour[i], tour[(i + 1) % n]);
+    let (c, d) = (tour[(i + j) % n], tour[(i + j + 1) % n]);
+    let (e, f) = (tour[(i + k) % n], tour[(i + k + 1) % n]);
+    let dist = |x:usize, y:usize| dist_graph[(x, y)];
+    let removed:f32 = dist(a, b) + dist(c, d) + dist(e, f);
+    let added:[f32; 7] = [
+        dist(a, c) + dist(b, d) + dist(e, f),
+        dist(a, b) + dist(c, e) + dist(d, f),
+        dist(a, e) + dist(d, c) + dist(b, f),
+        dist(a, d) + dist(e, b) + dist(c, f),
+        dist(a, d) + dist(e, c) + dist(b, f),
+        dist(a, e) + dist(d, b) + dist(c, f),
+        dist(a, c) + dist(b, e) + dist(d, f),
+    ];
+    (0..7).map(|case| (case, added[case] - removed))
+          .filter(|(_, delta)| *delta < -1e-4)
+          .min_by(|x, y| x.1.total_cmp(&y.1))
+}
+
+
+fn apply_three_opt(tour:&mut Vec<usize>, i:usize, j:usize, k:usize, case:usize) {
+    // Rebuilds the tour as a, the reconnected segments, then the rest of the tour back round to a
+    let mut rotated:Vec<usize> = tour.clone();
+    rotated.rotate_left((i + 1) % tour.len());
+    let (s1, rest) = rotated.split_at(j);
+    let (s2, rest) = rest.split_at(k - j);
+    let reversed = |segment:&[usize]| segment.iter().rev().copied().collect::<Vec<usize>>();
+    let (first, second):(Vec<usize>, Vec<usize>) = match case {
+        0 => (reversed(s1), s2.to_vec()),
+        1 => (s1.to_vec(), reversed(s2)),
+        2 => (reversed(s2), reversed(s1)),
+        3 => (s2.to_vec(), s1.to_vec()),
+        4 => (s2.to_vec(), reversed(s1)),
+        5 => (reversed(s2), s1.to_vec()),
+        _ => (reversed(s1), reversed(s2)),
+    };
+    *tour = first.into_iter().chain(second).chain(rest.iter().copied()).collect();
+}
+
+
+pub fn three_opt(tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+    /* 3-opt with segment reversal and pure exchange moves, applying the best reconnection of the
+       first improving triple of edges until the tour is a local optimum. Without candidate lists every
+       triple is tried, O(n^3) per pass. With them the first removed edge (a, b) must be replaced by an edge
+       from a to one of its candidates that is shorter than (a, b).
+       Reverses segments, so it is only valid for symmetric instances. Tours of 4 and 5 cities have no three
+       disjoint edges, their moves reuse an edge and reduce to 2-opt.
+       Returns true if the tour was improved */
+    let n:usize = tour.len();
+    let mut improved_any:bool = false;
+    if n < 4 {
+        return improved_any;
+    }
+    let mut positions:Vec<usize> = vec![0; n];
+    let mut improved:bool = true;
+    while improved {
+        improved = false;
+        for (pos, city_idx) in tour.iter().enumerate() {
+            positions[*city_idx] = pos;
+        }
+        'search: for i in 0..n {
+            // (j, k) offsets from i, 1 <= j < k <= n - 2 so both segments and the rest are not empty
+            let pairs:Vec<(usize, usize)> = match candidates {
+                None => (1..n - 2).flat_map(|j| (j + 1..n - 1).map(move |k| (j, k))).collect(),
+                Some(candidates) => {
+                    let (a, b) = (tour[i], tour[(i + 1) % n]);
+                    let mut pairs:Vec<(usize, usize)> = Vec::new();
+                    for x in candidates.neighbours(a).iter().copied() {
+                        if dist_graph[(a, x)] >= dist_graph[(a, b)] {
+                            break;
+                        }
+                        let offset:usize = (positions[x] + n - i) % n;
+                        // x is c, d or e of the move
+                        if (1..n - 2).contains(&offset) {
+                            pairs.extend((offset + 1..n - 1).map(|k| (offset, k)));
+                        }
+                        if (2..n - 1).contains(&offset) {
+                            pairs.extend((offset..n - 1).map(|k| (offset - 1, k)));
+                            pairs.extend((1..offset).map(|j| (j, offset)));
+                        }
+                    }
+                    pairs
+                },
+            };
+            for (j, k) in pairs {
+                if let Some((case, _)) = three_opt_move(tour, dist_graph, i, j, k) {
+                    apply_three_opt(tour, i, j, k, case);
+                    improved = true;
+                    improved_any = true;
+                    break 'search;
+                }
+            }
+        }
+    }
+    improved_any
+}
+
+
+pub trait LocalSearch: Send + Sync {
+
+    fn name(&self) -> String;
+
+    // Improves the tour in place and returns true if it changed
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool;
+
+    fn symmetric_only(&self) -> bool {
+        // Moves that reverse a segment change the cost of directed instances
+        true
+    }
+}
+
+
+pub struct NoLocalSearch;
+
+impl LocalSearch for NoLocalSearch {
+    fn name(&self) -> String {"none".to_string()}
+
+    fn improve(&self, _tour:&mut Vec<usize>, _dist_graph:&GraphMatrix, _candidates:Option<&CandidateLists>) -> bool {
+        false
+    }
+
+    fn symmetric_only(&self) -> bool {false}
+}
+
+
+pub struct TwoOpt(pub Improvement);
+
+impl LocalSearch for TwoOpt {
+    fn name(&self) -> String {"2-opt".to_string()}
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        two_opt(tour, dist_graph, candidates, self.0)
+    }
+}
+
+
+pub struct TwoHalfOpt(pub Improvement);
+
+impl LocalSearch for TwoHalfOpt {
+    fn name(&self) -> String {"2.5-opt".to_string()}
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        // 2-opt plus moving single cities, until neither improves the tour
+        let mut improved_any:bool = two_opt(tour, dist_graph, candidates, self.0);
+        while relocate_segments(tour, dist_graph, candidates, 1, false) {
+            improved_any = true;
+            two_opt(tour, dist_graph, candidates, self.0);
+        }
+        improved_any
+    }
+}
+
+
+pub struct OrOpt;
+
+impl LocalSearch for OrOpt {
+    fn name(&self) -> String {"or-opt".to_string()}
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        or_opt(tour, dist_graph, candidates)
+    }
+}
+
+
+pub struct OrOptDirected;
+
+impl LocalSearch for OrOptDirected {
+    fn name(&self) -> String {"directed or-opt".to_string()}
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        or_opt_directed(tour, dist_graph, candidates)
+    }
+
+    fn symmetric_only(&self) -> bool {false}
+}
+
+
+pub struct ThreeOpt;
+
+impl LocalSearch for ThreeOpt {
+    fn name(&self) -> String {"3-opt".to_string()}
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        three_opt(tour, dist_graph, candidates)
+    }
+}
+
+
+pub struct Chain(pub Vec<Box<dyn LocalSearch>>);
+
+impl LocalSearch for Chain {
+    fn name(&self) -> String {
+        self.0.iter().map(|search| search.name()).collect::<Vec<String>>().join(" + ")
+    }
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        // Runs every search in turn until a whole round leaves the tour unchanged
+        let mut improved_any:bool = false;
+        loop {
+            let mut improved:bool = false;
+            for search in self.0.iter() {
+                improved |= search.improve(tour, dist_graph, candidates);
+            }
+            if !improved {
+                return improved_any;
+            }
+            improved_any = true;
+            if self.0.len() < 2 {
+                return improved_any;
+            }
+        }
+    }
+
+    fn symmetric_only(&self) -> bool {
+        self.0.iter().any(|search| search.symmetric_only())
+    }
+}
+
+
+fn lk_neighbour(tour:&[usize], positions:&[usize], city_idx:usize, forward:bool) -> usize {
+    // The next city when walking the tour forwards, the previous one otherwise
+    let n:usize = tour.len();
+    let pos:usize = positions[city_idx];
+    if forward {tour[(pos + 1) % n]} else {tour[(pos + n - 1) % n]}
+}
+
+
+fn lk_move(tour:&mut [usize], positions:&mut [usize], dist_graph:&GraphMatrix, neighbours:&dyn Fn(usize) -> Vec<usize>,
+           t1:usize, forward:bool, limits:&LinKernighan) -> Option<Vec<usize>> {
+    /* Tries to improve the tour by breaking the edge from t1 towards its neighbour t2 and growing a chain of
+       2-opt flips: each step adds an edge (t2, t3), breaks the edge (t4, t3) and closes the tour with (t4, t1).
+       The chain stops once the running gain is used up or limits.max_depth flips are made, then the tour is rolled
+       back to the step that gave the largest gain. The first step tries the limits.breadth most promising t3s.
+       Returns the cities touched by an improving move, the tour is unchanged otherwise */
+    let t2:usize = lk_neighbour(tour, positions, t1, forward);
+    let g0:f32 = dist_graph[(t1, t2)];
+    let dist = |x:usize, y:usize| dist_graph[(x, y)];
+
+    // Candidates for the next t3 with their look-ahead gain, best first
+    let next_t3 = |tour:&[usize], positions:&[usize], t2:usize, forward:bool, gain:f32| -> Vec<(usize, f32)> {
+        let mut options:Vec<(usize, f32)> = neighbours(t2).into_iter()
+            .filter(|t3| *t3 != t1 && *t3 != t2 && *t3 != lk_neighbour(tour, positions, t2, forward))
+            .filter(|t3| gain - dist(t2, *t3) > 0.0)
+            .map(|t3| (t3, dist(lk_neighbour(tour, positions, t3, !forward), t3) - dist(t2, t3)))
+            .collect();
+        options.sort_by(|a, b| b.1.total_cmp(&a.1));
+        options
+    };
+
+    for (first_t3, _) in next_t3(tour, positions, t2, forward, g0).into_iter().take(limits.breadth) {
+        let mut flips:Vec<(usize, usize)> = Vec::new();
+        let mut added:Vec<(usize, usize)> = vec![(t2, first_t3)];
+        let mut touched:Vec<usize> = vec![t1, t2];
+        let (mut best_gain, mut best_len) = (0.0, 0);
+        let (mut cur_forward, mut cur_t2, mut t3, mut gain) = (forward, t2, first_t3, g0);
+        loop {
+            let t4:usize = lk_neighbour(tour, positions, t3, !cur_forward);
+            let (from_pos, to_pos) = if cur_forward {(positions[cur_t2], positions[t4])} else {(positions[t4], positions[cur_t2])};
+            reverse_segment(tour, positions, from_pos, to_pos);
+            flips.push((from_pos, to_pos));
+            touched.extend([t3, t4]);
+            gain += dist(t4, t3) - dist(cur_t2, t3);
+            if gain - dist(t4, t1) > best_gain + 1e-4 {
+                best_gain = gain - dist(t4, t1);
+                best_len = flips.len();
+            }
+            cur_forward = lk_neighbour(tour, positions, t1, true) == t4;
+            cur_t2 = t4;
+            if flips.len() >= limits.max_depth {
+                break;
+            }
+            // An edge added by this move must not be broken again
+            let next:Option<usize> = next_t3(tour, positions, cur_t2, cur_forward, gain).into_iter()
+                .map(|(x, _)| x)
+                .find(|x| {
+                    let broken:usize = lk_neighbour(tour, positions, *x, !cur_forward);
+                    !added.iter().any(|(a, b)| (*a == *x && *b == broken) || (*a == broken && *b == *x))
+                });
+            match next {
+                Some(x) => {
+                    added.push((cur_t2, x));
+                    t3 = x;
+                },
+                None => break,
+            }
+        }
+        for (from_pos, to_pos) in flips[best_len..].iter().rev() {
+            reverse_segment(tour, positions, *from_pos, *to_pos);
+        }
+        if best_len > 0 {
+            return Some(touched);
+        }
+    }
+    None
+}
+
+
+pub fn lin_kernighan(tour:&mut [usize], dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>, max_depth:usize, breadth:usize) -> bool {
+    /* Lin-Kernighan style local search built from chains of 2-opt flips, with don't-look bits.
+       Without candidate lists every city is a possible t3 at every step, O(n) per step.
+       Reverses segments, so it is only valid for symmetric instances.
+       Returns true if the tour was improved */
+    let n:usize = tour.len();
+    let mut improved_any:bool = false;
+    if n < 5 {
+        return improved_any;
+    }
+    let neighbours = |city_idx:usize| -> Vec<usize> {
+        match candidates {
+            Some(candidates) => candidates.neighbours(city_idx).to_vec(),
+            None => (0..n).collect(),
+        }
+    };
+    let mut positions:Vec<usize> = vec![0; n];
+    for (pos, city_idx) in tour.iter().enumerate() {
+        positions[*city_idx] = pos;
+    }
+    let limits = LinKernighan{max_depth, breadth};
+    let mut queue:VecDeque<usize> = tour.iter().copied().collect();
+    let mut queued:Vec<bool> = vec![true; n];
+
+    while let Some(t1) = queue.pop_front() {
+        queued[t1] = false;
+        for forward in [true, false] {
+            if let Some(touched) = lk_move(tour, &mut positions, dist_graph, &neighbours, t1, forward, &limits) {
+                improved_any = true;
+                for city_idx in touched {
+                    if !queued[city_idx] {
+                        queued[city_idx] = true;
+                        queue.push_back(city_idx);
+                    }
+                }
+                break;
+            }
+        }
+    }
+    improved_any
+}
+
+
+pub struct LinKernighan {
+    // Longest chain of flips in one move
+    pub max_depth: usize,
+    // Number of first steps tried before giving up on a city
+    pub breadth: usize,
+}
+
+impl Default for LinKernighan {
+    fn default() -> Self {
+        LinKernighan{max_depth:50, breadth:5}
+    }
+}
+
+impl LocalSearch for LinKernighan {
+    fn name(&self) -> String {"lin-kernighan".to_string()}
+
+    fn improve(&self, tour:&mut Vec<usize>, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>) -> bool {
+        lin_kernighan(tour, dist_graph, candidates, self.max_depth, self.breadth)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rand::seq::SliceRandom;
+
+    use super::*;
+    use crate::ant3::{get_distance_graph, get_tour_distance_graph};
+    use crate::city::City;
+    use crate::metric::DistanceMetric;
+    use crate::rng::{AntRng, colony_rng};
+    use crate::tour::Tour;
+
+    fn random_cities(num_cities:usize, rng:&mut AntRng) -> Vec<City> {
+        (0..num_cities).map(|i| City{name:(i + 1) as i32, x:rng.gen_range(0.0..100.0), y:rng.gen_range(0.0..100.0)}).collect()
+    }
+
+    fn asymmetric_graph(num_cities:usize) -> GraphMatrix {
+        // Every edge costs something different in each direction
+        GraphMatrix::from_fn(num_cities, |i, j| if i == j {0.0} else {((i * 37 + j * 11) % 23 + 1) as f32})
+    }
+
+    fn scrambled_tour(num_cities:usize) -> Vec<usize> {
+        // Visits the cities in steps of 7, a permutation as long as 7 does not divide num_cities
+        (0..num_cities).map(|i| i * 7 % num_cities).collect()
+    }
+
+    fn is_permutation(tour:&[usize], num_cities:usize) -> bool {
+        let mut sorted:Vec<usize> = tour.to_vec();
+        sorted.sort_unstable();
+        sorted == (0..num_cities).collect::<Vec<usize>>()
+    }
+
+    fn check_search(search:&dyn LocalSearch, dist_graph:&GraphMatrix, candidates:Option<&CandidateLists>, rng:&mut AntRng) {
+        // The improved tour still visits every city once and is never longer than the tour it started from
+        let num_cities:usize = dist_graph.size();
+        for _ in 0..5 {
+            let mut tour:Vec<usize> = (0..num_cities).collect();
+            tour.shuffle(rng);
+            let before:f32 = get_tour_distance_graph(&tour, dist_graph);
+            search.improve(&mut tour, dist_graph, candidates);
+            let after:f32 = get_tour_distance_graph(&tour, dist_graph);
+            assert!(Tour::new(tour, num_cities).is_ok(), "{} did not return a permutation", search.name());
+            assert!(after <= before + 1e-3, "{} made the tour longer, {} -> {}", search.name(), before, after);
+        }
+    }
+
+    fn symmetric_searches() -> Vec<Box<dyn LocalSearch>> {
+        vec![Box::new(TwoOpt(Improvement::First)), Box::new(TwoOpt(Improvement::Best)), Box::new(TwoHalfOpt(Improvement::First)),
+             Box::new(OrOpt), Box::new(ThreeOpt), Box::new(LinKernighan::default()),
+             Box::new(Chain(vec![Box::new(TwoOpt(Improvement::First)), Box::new(OrOpt)]))]
+    }
+
+    #[test]
+    fn symmetric_searches_keep_a_shorter_permutation() {
+        let mut rng:AntRng = colony_rng(Some(1));
+        // Small tours hit the guards for too few cities, 40 exercises the moves themselves
+        for num_cities in [1, 2, 3, 4, 5, 6, 40] {
+            let dist_graph:GraphMatrix = get_distance_graph(&random_cities(num_cities, &mut rng), DistanceMetric::Euclidean);
+            let candidates:CandidateLists = CandidateLists::nearest(&dist_graph, 5.min(num_cities.saturating_sub(1)).max(1));
+            for search in symmetric_searches() {
+                check_search(search.as_ref(), &dist_graph, None, &mut rng);
+                check_search(search.as_ref(), &dist_graph, Some(&candidates), &mut rng);
+            }
+        }
+    }
+
+    #[test]
+    fn directed_or_opt_keeps_a_shorter_permutation() {
+        for num_cities in [3, 4, 5, 6, 30] {
+            let dist_graph:GraphMatrix = asymmetric_graph(num_cities);
+            let mut tour:Vec<usize> = scrambled_tour(num_cities);
+            let before:f32 = get_tour_distance_graph(&tour, &dist_graph);
+            or_opt_directed(&mut tour, &dist_graph, None);
+            assert!(is_permutation(&tour, num_cities));
+            assert!(get_tour_distance_graph(&tour, &dist_graph) <= before);
+        }
+    }
+
+    #[test]
+    fn directed_or_opt_keeps_a_permutation_with_candidates() {
+        let mut rng:AntRng = colony_rng(Some(2));
+        let dist_graph:GraphMatrix = GraphMatrix::from_fn(30, |i, j| if i == j {0.0} else {rng.gen_range(1.0..100.0)});
+        let candidates:CandidateLists = CandidateLists::nearest(&dist_graph, 5);
+        assert!(!OrOptDirected.symmetric_only());
+        check_search(&OrOptDirected, &dist_graph, None, &mut rng);
+        check_search(&OrOptDirected, &dist_graph, Some(&candidates), &mut rng);
+    }
+
+    #[test]
+    fn directed_or_opt_reaches_a_local_optimum() {
+        let dist_graph:GraphMatrix = asymmetric_graph(30);
+        let mut tour:Vec<usize> = scrambled_tour(30);
+        assert!(or_opt_directed(&mut tour, &dist_graph, None));
+        assert!(!or_opt_directed(&mut tour, &dist_graph, None));
+    }
+
+    #[test]
+    fn two_opt_leaves_no_improving_move() {
+        let mut rng:AntRng = colony_rng(Some(3));
+        let dist_graph:GraphMatrix = get_distance_graph(&random_cities(40, &mut rng), DistanceMetric::Euclidean);
+        for improvement in [Improvement::First, Improvement::Best] {
+            let mut tour:Vec<usize> = (0..40).collect();
+            tour.shuffle(&mut rng);
+            assert!(two_opt(&mut tour, &dist_graph, None, improvement));
+            assert!(is_permutation(&tour, 40));
+            let length:f32 = get_tour_distance_graph(&tour, &dist_graph);
+            for i in 0..tour.len() - 1 {
+                for j in i + 1..tour.len() {
+                    let mut flipped:Vec<usize> = tour.clone();
+                    flipped[i..=j].reverse();
+                    assert!(get_tour_distance_graph(&flipped, &dist_graph) >= length - 1e-3);
+                }
+            }
+        }
+    }
+}
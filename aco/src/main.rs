@@ -1,37 +1,612 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-// use std::collections::HashMap;
-// use rand::Rng;
-// use random_choice::random_choice;
-use std::sync::{Arc, Mutex};
-use once_cell::sync::Lazy;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rand::Rng;
 
-mod city;
-use city::City;
+use aco::ant3::{cities_from_coordinates3, is_symmetric, get_short_path_indicies, get_distance_graph, GraphMatrix};
+use aco::city::City;
+use aco::config::AcoConfig;
+use aco::error::read_file;
+use aco::candidates::CandidateLists;
+use aco::local_search::{LocalSearch, Improvement, NoLocalSearch, TwoOpt, TwoHalfOpt, OrOpt, ThreeOpt, LinKernighan, Chain};
+use aco::metric::DistanceMetric;
+use aco::mmas::MmasConfig;
+use aco::rng::colony_rng;
+use aco::solver::{Instance, Solver, SolveResult, Aco3Solver, MmasSolver, AntColonySolver, AcoSolver};
+use aco::strategy::{PheromoneStrategy, AntColonySystem, LocalUpdate, GlobalUpdate, DepositSchedule, AntSystem, ElitistAntSystem, RankBasedAntSystem};
+use aco::tour::Tour;
+use aco::tsplib::{read_tsplib, parse_opt_tour};
 
-use std::fs;
-use std::time::{Instant};
 
-mod ant3;
-use crate::ant3::{cities_from_coordinates3, ACO3, get_short_path_indicies, get_pheromone_graph, get_distance_graph};
+#[derive(Parser)]
+#[command(name = "aco", about = "Ant colony optimisation for the travelling salesman problem")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
 
+#[derive(Subcommand)]
+enum Command {
+    /// Run an ant colony on an instance and print the best tour found
+    Solve(Box<SolveArgs>),
+    /// Print the length of a tour file on an instance
+    Evaluate(EvaluateArgs),
+    /// Improve a tour file with local search
+    Polish(PolishArgs),
+    /// Write a random instance
+    Generate(GenerateArgs),
+}
 
 
-fn main() {
-    let now = Instant::now();
-    
-    let cities:Vec<City> = cities_from_coordinates3("coordinates.txt");
-    let shortest_path_idx:Vec<usize> = get_short_path_indicies("shortest_path.txt");
-    let pher_graph = get_pheromone_graph(&cities, 0.0005);
-    let dist_graph = get_distance_graph(&cities);
-    let best_tour:Vec<usize> = {
-        let mut aco = ACO3::new(&cities, pher_graph, dist_graph, 100,10, shortest_path_idx);
-        aco.optimize()
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    /// One "x, y" pair per line
+    Coords,
+    /// TSPLIB .tsp / .atsp file
+    Tsplib,
+}
+
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Algorithm {
+    /// Ant colony on the distance matrix (ACO3), the pheromone rule is chosen with --strategy
+    Acs,
+    /// Ant Colony System on the HashMap graph with 2-opt (ACO)
+    Aco,
+    /// MAX-MIN Ant System on the distance matrix, --alpha is the evaporation rate
+    Mmas,
+    /// Plain Ant System on the distance matrix (AntColony)
+    As,
+}
+
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Strategy {
+    /// Ant Colony System
+    Acs,
+    /// Ant System
+    As,
+    /// Elitist Ant System
+    Eas,
+    /// Rank-based Ant System
+    Ras,
+}
+
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AcsLocalUpdate {
+    /// Decay each edge as soon as an ant crosses it, ants then build their tours one after another and --parallel is rejected
+    PerStep,
+    /// Decay every edge leaving the cities of each finished tour, reproduces earlier results
+    EndOfTour,
+}
+
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AcsGlobalUpdate {
+    /// Decay and deposit on the depositing tour's edges only
+    BestTour,
+    /// Evaporate every edge, then deposit on the depositing tour's edges
+    EvaporateAll,
+    /// Decay every edge leaving the best tour's cities, reproduces earlier results
+    AllEdges,
+}
+
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LocalSearchKind {
+    /// No local search, not even on asymmetric instances
+    None,
+    /// 2-opt with don't-look bits
+    TwoOpt,
+    /// 2-opt plus moving single cities
+    TwoHalfOpt,
+    /// Moving segments of 1 to 3 cities in either orientation
+    OrOpt,
+    /// Segment reversal and exchange of two segments
+    ThreeOpt,
+    /// Lin-Kernighan style chains of 2-opt flips
+    LinKernighan,
+}
+
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Deposit {
+    /// The best tour of each iteration deposits
+    IterationBest,
+    /// The best tour found so far deposits
+    BestSoFar,
+}
+
+
+#[derive(Args)]
+struct InstanceArgs {
+    /// Instance file
+    input: PathBuf,
+    /// Input format, guessed from the file extension when not given
+    #[arg(long, value_enum)]
+    format: Option<InputFormat>,
+    /// Distance metric: euclidean, manhattan, chebyshev, haversine, nint, ceil, att or geo.
+    /// Defaults to euclidean for coordinate files and the EDGE_WEIGHT_TYPE for TSPLIB files
+    #[arg(long, value_parser = parse_metric)]
+    metric: Option<DistanceMetric>,
+}
+
+
+#[derive(Args)]
+struct SolveArgs {
+    #[command(flatten)]
+    instance: InstanceArgs,
+    #[arg(long, value_enum, default_value = "acs")]
+    algorithm: Algorithm,
+    /// Pheromone update rule of the acs algorithm, defaults to acs. The Ant System rules use --alpha as the evaporation rate
+    #[arg(long, value_enum)]
+    strategy: Option<Strategy>,
+    /// Asymmetric instances always use directed or-opt unless none is given
+    #[command(flatten)]
+    local_search: LocalSearchArgs,
+    /// EAS: weight of the best-so-far tour's deposit, defaults to the number of cities
+    #[arg(long)]
+    elitist_weight: Option<f32>,
+    /// RAS: number of ranks, the best rank-width - 1 ants and the best-so-far tour deposit
+    #[arg(long, default_value_t = 6)]
+    rank_width: usize,
+    /// ACS: when the local pheromone update is applied
+    #[arg(long, value_enum, default_value = "per-step")]
+    local_update: AcsLocalUpdate,
+    /// ACS: which edges the global pheromone update changes
+    #[arg(long, value_enum, default_value = "best-tour")]
+    global_update: AcsGlobalUpdate,
+    #[arg(long, default_value_t = 100)]
+    iterations: usize,
+    #[arg(long, default_value_t = 10)]
+    ants: usize,
+    #[arg(long, default_value_t = 0.90)]
+    q0: f32,
+    /// Pheromone decay of the global update
+    #[arg(long, default_value_t = 0.1)]
+    alpha: f32,
+    #[arg(long, default_value_t = 0.20)]
+    beta: f32,
+    /// Pheromone decay of the local update
+    #[arg(long, default_value_t = 0.1)]
+    rho: f32,
+    /// Initial pheromone
+    #[arg(long, default_value_t = 0.0005)]
+    tau: f32,
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Build tours on the rayon thread pool, the acs strategy needs --local-update end-of-tour for this
+    #[arg(long)]
+    parallel: bool,
+    /// Worker threads for --parallel, defaults to one per core
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+    /// Ants only consider the k nearest unvisited cities, and every unvisited city once those are used
+    #[arg(long, value_name = "K")]
+    candidates: Option<usize>,
+    /// Spread the candidates over the four quadrants around each city
+    #[arg(long, requires = "candidates")]
+    quadrant: bool,
+    /// Stop after this many seconds
+    #[arg(long)]
+    time_limit: Option<f64>,
+    /// Stop after this many tours have been built
+    #[arg(long)]
+    max_evaluations: Option<usize>,
+    /// Stop after this many iterations without a shorter tour
+    #[arg(long)]
+    stagnation: Option<usize>,
+    /// Stop once a tour at least this short is found
+    #[arg(long)]
+    target: Option<f32>,
+    /// MMAS: probability of rebuilding the best tour once the trail has converged
+    #[arg(long, default_value_t = 0.05)]
+    p_best: f32,
+    /// MMAS, ACS and ACO: which tour deposits pheromone, iteration-best for MMAS and best-so-far otherwise
+    #[arg(long, value_enum)]
+    deposit: Option<Deposit>,
+    /// MMAS, ACS and ACO: let the best-so-far tour deposit every this many iterations, the iteration-best otherwise
+    #[arg(long)]
+    best_so_far_every: Option<usize>,
+    /// MMAS: reset a converged trail after this many iterations without improvement, 0 never resets
+    #[arg(long, default_value_t = 50)]
+    reinit_after: usize,
+    /// Known shortest tour, the run stops once a tour of the same length is found
+    #[arg(long)]
+    reference: Option<PathBuf>,
+    /// Write the best tour to this file
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+
+#[derive(Args)]
+struct LocalSearchArgs {
+    /// Improve every tour, a comma separated list is run in turn until none improves the tour
+    #[arg(long, value_enum, value_delimiter = ',')]
+    local_search: Vec<LocalSearchKind>,
+    /// 2-opt applies the best move around a city instead of the first one found
+    #[arg(long)]
+    best_improvement: bool,
+    /// Lin-Kernighan: longest chain of flips in one move
+    #[arg(long, default_value_t = 50)]
+    lk_depth: usize,
+    /// Lin-Kernighan: number of first steps tried from each city
+    #[arg(long, default_value_t = 5)]
+    lk_breadth: usize,
+}
+
+
+#[derive(Args)]
+struct PolishArgs {
+    #[command(flatten)]
+    instance: InstanceArgs,
+    /// Tour file, either city numbers starting from 1 or a TSPLIB .tour file
+    tour: PathBuf,
+    /// Defaults to lin-kernighan
+    #[command(flatten)]
+    local_search: LocalSearchArgs,
+    /// Length of the nearest neighbour lists the searches use, 0 tries every city
+    #[arg(long, value_name = "K", default_value_t = 10)]
+    candidates: usize,
+    /// Write the improved tour to this file
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+
+#[derive(Args)]
+struct EvaluateArgs {
+    #[command(flatten)]
+    instance: InstanceArgs,
+    /// Tour file, either city numbers starting from 1 or a TSPLIB .tour file
+    tour: PathBuf,
+}
+
+
+#[derive(Args)]
+struct GenerateArgs {
+    /// Number of cities
+    #[arg(long, default_value_t = 50)]
+    cities: usize,
+    /// Coordinates are drawn from [0, width) x [0, height)
+    #[arg(long, default_value_t = 100.0)]
+    width: f64,
+    #[arg(long, default_value_t = 100.0)]
+    height: f64,
+    /// Round the coordinates down to whole numbers
+    #[arg(long)]
+    integer: bool,
+    #[arg(long)]
+    seed: Option<u64>,
+    #[arg(long, value_enum, default_value = "coords")]
+    format: InputFormat,
+    /// Output file, stdout when not given
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+
+fn parse_metric(name:&str) -> Result<DistanceMetric, String> {
+    DistanceMetric::from_name(name).ok_or(format!("unknown metric '{}'", name))
+}
+
+
+fn path_str(path:&Path) -> Result<&str, String> {
+    path.to_str().ok_or(format!("{} is not a valid UTF-8 path", path.display()))
+}
+
+
+fn guess_format(path:&Path) -> InputFormat {
+    // TSPLIB files are recognised by their extension, everything else is read as coordinates
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tsp") | Some("atsp") => InputFormat::Tsplib,
+        _ => InputFormat::Coords,
+    }
+}
+
+
+fn load_instance(args:&InstanceArgs) -> Result<Instance, Box<dyn Error>> {
+    // Returns the cities, the distance matrix and the metric it was built with
+    let file_path:&str = path_str(&args.input)?;
+    match args.format.unwrap_or_else(|| guess_format(&args.input)) {
+        InputFormat::Coords => {
+            let cities:Vec<City> = cities_from_coordinates3(file_path)?;
+            let metric:DistanceMetric = args.metric.unwrap_or_default();
+            let dist_graph:GraphMatrix = get_distance_graph(&cities, metric);
+            Ok(Instance::new(cities, dist_graph, Some(metric))?)
+        },
+        InputFormat::Tsplib => {
+            let instance = read_tsplib(file_path)?;
+            match (args.metric, instance.metric) {
+                (Some(_), None) => Err(format!("{} has an explicit distance matrix, --metric cannot be used", file_path).into()),
+                (Some(metric), Some(_)) => {
+                    let dist_graph:GraphMatrix = get_distance_graph(&instance.cities, metric);
+                    Ok(Instance::new(instance.cities, dist_graph, Some(metric))?)
+                },
+                (None, _) => Ok(Instance::from(instance)),
+            }
+        },
+    }
+}
+
+
+fn read_tour(path:&Path, num_cities:usize) -> Result<Tour, Box<dyn Error>> {
+    // Reads a TSPLIB tour file or a file of city numbers like shortest_path.txt
+    // and checks it visits each of the num_cities cities exactly once
+    let file_path:&str = path_str(path)?;
+    let contents:String = read_file(file_path)?;
+    let cities:Vec<usize> = if contents.contains("TOUR_SECTION") {
+        parse_opt_tour(&contents)?
+    } else {
+        get_short_path_indicies(file_path)?
+    };
+    Ok(Tour::new(cities, num_cities)?)
+}
+
+
+fn local_search_from_args(args:&LocalSearchArgs) -> Option<Box<dyn LocalSearch>> {
+    // A single search is used as it is, several are chained
+    let improvement:Improvement = if args.best_improvement {Improvement::Best} else {Improvement::First};
+    let mut searches:Vec<Box<dyn LocalSearch>> = args.local_search.iter().map(|kind| -> Box<dyn LocalSearch> {
+        match kind {
+            LocalSearchKind::None => Box::new(NoLocalSearch),
+            LocalSearchKind::TwoOpt => Box::new(TwoOpt(improvement)),
+            LocalSearchKind::TwoHalfOpt => Box::new(TwoHalfOpt(improvement)),
+            LocalSearchKind::OrOpt => Box::new(OrOpt),
+            LocalSearchKind::ThreeOpt => Box::new(ThreeOpt),
+            LocalSearchKind::LinKernighan => Box::new(LinKernighan{max_depth:args.lk_depth, breadth:args.lk_breadth}),
+        }
+    }).collect();
+    match searches.len() {
+        0 => None,
+        1 => searches.pop(),
+        _ => Some(Box::new(Chain(searches))),
+    }
+}
+
+
+fn tour_to_string(tour:&[usize]) -> String {
+    // City numbers start from 1, the same layout as shortest_path.txt
+    tour.iter().map(|city_idx| (city_idx + 1).to_string()).collect::<Vec<String>>().join(" ")
+}
+
+
+fn check_algorithm_flags(args:&SolveArgs) -> Result<(), String> {
+    // Per-step ants see each other's trail, so they cannot be spread over threads
+    let acs_strategy:bool = matches!(args.algorithm, Algorithm::Acs) && matches!(args.strategy.unwrap_or(Strategy::Acs), Strategy::Acs);
+    if args.parallel && acs_strategy && matches!(args.local_update, AcsLocalUpdate::PerStep) {
+        return Err("--parallel cannot be used with --local-update per-step, use --local-update end-of-tour".to_string());
+    }
+    // Flags the chosen algorithm has no use for are an error rather than silently ignored
+    let mut unused:Vec<&str> = Vec::new();
+    if args.strategy.is_some() && !matches!(args.algorithm, Algorithm::Acs) {
+        unused.push("--strategy");
+    }
+    if !args.local_search.local_search.is_empty() && matches!(args.algorithm, Algorithm::Aco | Algorithm::As) {
+        unused.push("--local-search");
+    }
+    if args.reference.is_some() && matches!(args.algorithm, Algorithm::Mmas | Algorithm::As) {
+        unused.push("--reference");
+    }
+    if args.candidates.is_some() && matches!(args.algorithm, Algorithm::Aco) {
+        unused.push("--candidates");
+    }
+    if unused.is_empty() {
+        return Ok(());
+    }
+    let algorithm = args.algorithm.to_possible_value().expect("every algorithm has a name");
+    let hint:&str = if matches!(args.algorithm, Algorithm::As) {", use --algorithm acs --strategy as for those"} else {""};
+    Err(format!("--algorithm {} cannot use {}{}", algorithm.get_name(), unused.join(", "), hint))
+}
+
+
+fn deposit_schedule(args:&SolveArgs, default:Deposit) -> DepositSchedule {
+    // --best-so-far-every takes precedence over --deposit, each algorithm has its own default
+    match (args.best_so_far_every, args.deposit.unwrap_or(default)) {
+        (Some(every), _) => DepositSchedule::Schedule(every),
+        (None, Deposit::IterationBest) => DepositSchedule::IterationBest,
+        (None, Deposit::BestSoFar) => DepositSchedule::BestSoFar,
+    }
+}
+
+
+fn solve(args:SolveArgs) -> Result<(), Box<dyn Error>> {
+    check_algorithm_flags(&args)?;
+    let instance:Instance = load_instance(&args.instance)?;
+
+    let mut builder = AcoConfig::builder()
+                      .iterations(args.iterations)
+                      .num_ants(args.ants)
+                      .q0(args.q0)
+                      .alpha(args.alpha)
+                      .beta(args.beta)
+                      .rho(args.rho)
+                      .tau(args.tau)
+                      .parallel(args.parallel)
+                      .quadrant_candidates(args.quadrant);
+    if let Some(seed) = args.seed {
+        builder = builder.seed(seed);
+    }
+    if let Some(seconds) = args.time_limit {
+        let time_limit = Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid time limit {}", seconds))?;
+        builder = builder.time_limit(time_limit);
+    }
+    if let Some(threads) = args.threads {
+        builder = builder.threads(threads);
+    }
+    if let Some(k) = args.candidates {
+        builder = builder.candidates(k);
+    }
+    if let Some(max_evaluations) = args.max_evaluations {
+        builder = builder.max_evaluations(max_evaluations);
+    }
+    if let Some(iterations) = args.stagnation {
+        builder = builder.stagnation(iterations);
+    }
+    if let Some(target) = args.target {
+        builder = builder.target_distance(target);
+    }
+    let config:AcoConfig = builder.build()?;
+
+    let shortest_tour:Option<Tour> = match &args.reference {
+        Some(path) => Some(read_tour(path, instance.num_cities())?),
+        None => None,
+    };
+
+    let solver:Box<dyn Solver> = match args.algorithm {
+        Algorithm::Acs => {
+            let strategy:Box<dyn PheromoneStrategy> = match args.strategy.unwrap_or(Strategy::Acs) {
+                Strategy::Acs => {
+                    let local_update:LocalUpdate = match args.local_update {
+                        AcsLocalUpdate::PerStep => LocalUpdate::PerStep,
+                        AcsLocalUpdate::EndOfTour => LocalUpdate::EndOfTour,
+                    };
+                    let global_update:GlobalUpdate = match args.global_update {
+                        AcsGlobalUpdate::BestTour => GlobalUpdate::BestTourEdges,
+                        AcsGlobalUpdate::EvaporateAll => GlobalUpdate::EvaporateAll,
+                        AcsGlobalUpdate::AllEdges => GlobalUpdate::AllEdgesFromTour,
+                    };
+                    Box::new(AntColonySystem::new(local_update, global_update, deposit_schedule(&args, Deposit::BestSoFar))?)
+                },
+                Strategy::As => Box::new(AntSystem),
+                Strategy::Eas => Box::new(ElitistAntSystem::new(args.elitist_weight)?),
+                Strategy::Ras => Box::new(RankBasedAntSystem::new(args.rank_width)?),
+            };
+            Box::new(Aco3Solver{strategy, local_search:local_search_from_args(&args.local_search), reference:shortest_tour})
+        },
+        Algorithm::Mmas => {
+            let mmas_config:MmasConfig = MmasConfig::builder()
+                                         .p_best(args.p_best)
+                                         .deposit(deposit_schedule(&args, Deposit::IterationBest))
+                                         .reinit_after(Some(args.reinit_after).filter(|after| *after > 0))
+                                         .build()?;
+            Box::new(MmasSolver{mmas_config, local_search:local_search_from_args(&args.local_search)})
+        },
+        Algorithm::Aco => {
+            if instance.metric.is_none() {
+                return Err("the aco algorithm needs city coordinates, use --algorithm acs".into());
+            }
+            Box::new(AcoSolver{deposit:deposit_schedule(&args, Deposit::BestSoFar), reference:shortest_tour})
+        },
+        Algorithm::As => Box::new(AntColonySolver),
     };
-    println!("\nbest_tour -> {:?}", best_tour);
 
-    println!("\nelapsed time -> {} secs", now.elapsed().as_secs());
-    
-    
+    println!("solver -> {}", solver.name());
+    let result:SolveResult = solver.solve(&instance, config)?;
+    let best_tour:Tour = result.tour;
+
+    println!("\nstopped -> {}, iterations -> {}", result.stop_reason, result.iterations);
+    if let Some(strategy) = result.strategy {
+        println!("strategy -> {}", strategy);
+    }
+    if let Some(local_search) = &result.local_search {
+        println!("local search -> {}", local_search);
+    }
+    if let Some(reinitialisations) = result.reinitialisations {
+        println!("reinitialisations -> {}", reinitialisations);
+    }
+    println!("best_tour -> {}", tour_to_string(best_tour.cities()));
+    println!("best_tour_distance -> {:.2}", result.distance);
+    println!("elapsed time -> {:.2} secs", result.elapsed.as_secs_f64());
+
+    if let Some(path) = &args.output {
+        fs::write(path, tour_to_string(best_tour.cities()) + "\n").map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+
+fn evaluate(args:EvaluateArgs) -> Result<(), Box<dyn Error>> {
+    let Instance{cities, dist_graph, ..} = load_instance(&args.instance)?;
+    let tour:Tour = read_tour(&args.tour, cities.len())?;
+    println!("{:.2}", tour.distance(&dist_graph));
+    Ok(())
+}
+
+
+fn polish(args:PolishArgs) -> Result<(), Box<dyn Error>> {
+    let now = Instant::now();
+    let Instance{cities, dist_graph, ..} = load_instance(&args.instance)?;
+    let tour:Tour = read_tour(&args.tour, cities.len())?;
+    let local_search:Box<dyn LocalSearch> = local_search_from_args(&args.local_search).unwrap_or_else(|| Box::new(LinKernighan::default()));
+    if local_search.symmetric_only() && !is_symmetric(&dist_graph) {
+        return Err(format!("{} reverses segments and needs a symmetric instance", local_search.name()).into());
     }
+    let candidates:Option<CandidateLists> = Some(args.candidates).filter(|k| *k > 0).map(|k| CandidateLists::nearest(&dist_graph, k));
 
+    let mut cities_idx:Vec<usize> = tour.cities().to_vec();
+    local_search.improve(&mut cities_idx, &dist_graph, candidates.as_ref());
+    let polished:Tour = Tour::new(cities_idx, cities.len())?;
+    println!("tour_distance -> {:.2}", tour.distance(&dist_graph));
+    println!("polished_distance -> {:.2}", polished.distance(&dist_graph));
+    println!("elapsed time -> {:.2} secs", now.elapsed().as_secs_f64());
+    if let Some(path) = &args.output {
+        fs::write(path, tour_to_string(polished.cities()) + "\n").map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+
+fn generate(args:GenerateArgs) -> Result<(), Box<dyn Error>> {
+    if args.cities == 0 {
+        return Err("--cities must be at least 1".into());
+    }
+    if !(args.width > 0.0 && args.height > 0.0) {
+        return Err("--width and --height must be positive".into());
+    }
+    let mut rng = colony_rng(args.seed);
+    let cities:Vec<City> = (0..args.cities).map(|i| {
+        let mut x:f64 = rng.gen_range(0.0..args.width);
+        let mut y:f64 = rng.gen_range(0.0..args.height);
+        if args.integer {
+            x = x.floor();
+            y = y.floor();
+        }
+        City{name:(i+1) as i32, x, y}
+    }).collect();
+
+    let contents:String = match args.format {
+        InputFormat::Coords => cities.iter().map(|city| format!("{}, {}\n", city.x, city.y)).collect(),
+        InputFormat::Tsplib => {
+            let mut contents:String = format!("NAME : random{}\nTYPE : TSP\nDIMENSION : {}\nEDGE_WEIGHT_TYPE : EUC_2D\nNODE_COORD_SECTION\n",
+                                              args.cities, args.cities);
+            for city in cities.iter() {
+                contents.push_str(&format!("{} {} {}\n", city.name, city.x, city.y));
+            }
+            contents.push_str("EOF\n");
+            contents
+        },
+    };
+    match &args.output {
+        Some(path) => fs::write(path, contents).map_err(|e| format!("cannot write {}: {}", path.display(), e).into()),
+        None => {
+            print!("{}", contents);
+            Ok(())
+        },
+    }
+}
+
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Solve(args) => solve(*args),
+        Command::Evaluate(args) => evaluate(args),
+        Command::Polish(args) => polish(args),
+        Command::Generate(args) => generate(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        },
+    }
+}
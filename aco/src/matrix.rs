@@ -0,0 +1,156 @@
+use std::ops::{Index, IndexMut};
+
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Matrix<T> {
+    size: usize,
+    symmetric: bool,
+    data: Vec<T>,
+}
+
+
+impl<T: Copy> Matrix<T> {
+
+    pub fn new(size:usize, value:T) -> Matrix<T> {
+        // A size x size matrix stored row by row in one allocation
+        Matrix{size, symmetric:false, data:vec![value; size * size]}
+    }
+
+    pub fn new_symmetric(size:usize, value:T) -> Matrix<T> {
+        // Stores only the upper triangle, [(i, j)] and [(j, i)] are the same entry
+        Matrix{size, symmetric:true, data:vec![value; size * (size + 1) / 2]}
+    }
+
+    pub fn from_fn<F: FnMut(usize, usize) -> T>(size:usize, mut f:F) -> Matrix<T> {
+        let data:Vec<T> = (0..size).flat_map(|i| (0..size).map(move |j| (i, j))).map(|(i, j)| f(i, j)).collect();
+        Matrix{size, symmetric:false, data}
+    }
+
+    pub fn from_fn_symmetric<F: FnMut(usize, usize) -> T>(size:usize, mut f:F) -> Matrix<T> {
+        // f is only called with i <= j
+        let data:Vec<T> = (0..size).flat_map(|i| (i..size).map(move |j| (i, j))).map(|(i, j)| f(i, j)).collect();
+        Matrix{size, symmetric:true, data}
+    }
+
+    // Number of rows, and of columns
+    pub fn size(&self) -> usize {self.size}
+    // True when only the upper triangle is stored
+    pub fn is_symmetric_storage(&self) -> bool {self.symmetric}
+
+    #[inline]
+    fn offset(&self, i:usize, j:usize) -> usize {
+        if self.symmetric {
+            let (i, j) = if i <= j {(i, j)} else {(j, i)};
+            // Row i of the upper triangle starts after n + (n - 1) + ... + (n - i + 1) entries
+            i * self.size - i * (i.saturating_sub(1)) / 2 + (j - i)
+        } else {
+            i * self.size + j
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, i:usize, j:usize) -> T {
+        assert!(i < self.size && j < self.size, "index ({}, {}) out of bounds for a {}x{} matrix", i, j, self.size, self.size);
+        self.data[self.offset(i, j)]
+    }
+
+    /// # Safety
+    /// `i` and `j` must both be less than `size()`.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, i:usize, j:usize) -> T {
+        *self.data.get_unchecked(self.offset(i, j))
+    }
+
+    #[inline]
+    pub fn set(&mut self, i:usize, j:usize, value:T) {
+        self[(i, j)] = value;
+    }
+
+    pub fn row(&self, i:usize) -> impl Iterator<Item = T> + '_ {
+        // The entries of row i in column order, in either storage mode
+        (0..self.size).map(move |j| self.get(i, j))
+    }
+
+    pub fn values_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        // Every stored entry once, a symmetric matrix yields each (i, j) pair with i <= j
+        self.data.iter_mut()
+    }
+
+    pub fn map<F: FnMut(usize, usize, T) -> T>(&mut self, mut f:F) {
+        // Replaces every stored entry with f(i, j, entry), called with i <= j for symmetric storage
+        for i in 0..self.size {
+            let start:usize = if self.symmetric {i} else {0};
+            for j in start..self.size {
+                let offset:usize = self.offset(i, j);
+                self.data[offset] = f(i, j, self.data[offset]);
+            }
+        }
+    }
+}
+
+
+impl<T: Copy> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, (i, j):(usize, usize)) -> &T {
+        assert!(i < self.size && j < self.size, "index ({}, {}) out of bounds for a {}x{} matrix", i, j, self.size, self.size);
+        &self.data[self.offset(i, j)]
+    }
+}
+
+
+impl<T: Copy> IndexMut<(usize, usize)> for Matrix<T> {
+    #[inline]
+    fn index_mut(&mut self, (i, j):(usize, usize)) -> &mut T {
+        assert!(i < self.size && j < self.size, "index ({}, {}) out of bounds for a {}x{} matrix", i, j, self.size, self.size);
+        let offset:usize = self.offset(i, j);
+        &mut self.data[offset]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_storage_shares_both_halves() {
+        let mut matrix:Matrix<u32> = Matrix::new_symmetric(4, 0);
+        matrix[(3, 1)] = 5;
+        matrix.set(0, 2, 7);
+        assert_eq!((matrix.get(1, 3), matrix[(3, 1)]), (5, 5));
+        assert_eq!((matrix.get(2, 0), matrix[(0, 2)]), (7, 7));
+        assert_eq!(matrix.values_mut().count(), 10);
+    }
+
+    #[test]
+    fn symmetric_offsets_are_distinct() {
+        // Every entry of the upper triangle, diagonal included, has its own slot
+        let size:usize = 5;
+        let matrix:Matrix<usize> = Matrix::from_fn_symmetric(size, |i, j| i * size + j);
+        for i in 0..size {
+            for j in 0..size {
+                assert_eq!(matrix[(i, j)], i.min(j) * size + i.max(j));
+                assert_eq!(matrix.row(i).nth(j), Some(matrix[(i, j)]));
+            }
+        }
+    }
+
+    #[test]
+    fn map_visits_each_stored_entry_once() {
+        let mut full:Matrix<u32> = Matrix::new(3, 0);
+        let mut symmetric:Matrix<u32> = Matrix::new_symmetric(3, 0);
+        full.map(|_, _, value| value + 1);
+        symmetric.map(|i, j, value| {assert!(i <= j); value + 1});
+        assert!((0..3).all(|i| (0..3).all(|j| full[(i, j)] == 1 && symmetric[(i, j)] == 1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn symmetric_index_is_bounds_checked() {
+        // (0, 3) would land inside the triangle's storage, the check has to use size
+        let matrix:Matrix<u32> = Matrix::new_symmetric(3, 0);
+        matrix.get(0, 3);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::city::City;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMetric {
+    #[default]
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    // Great-circle distance in km, x is the latitude and y the longitude in decimal degrees
+    Haversine,
+    // TSPLIB EUC_2D, Euclidean distance rounded to the nearest integer
+    Nint,
+    // TSPLIB CEIL_2D, Euclidean distance rounded up
+    Ceil,
+    // TSPLIB ATT pseudo-Euclidean distance
+    Att,
+    // TSPLIB GEO distance, x and y are latitude and longitude in DDD.MM format
+    Geo,
+}
+
+
+fn nint(x:f64) -> f64 {
+    // TSPLIB nearest integer, (int)(x + 0.5)
+    (x + 0.5).floor()
+}
+
+
+fn geo_radians(coord:f64) -> f64 {
+    // Converts a TSPLIB DDD.MM coordinate into radians
+    // TSPLIB uses this truncated value of pi, the optimal tour lengths depend on it
+    #[allow(clippy::approx_constant)]
+    let pi:f64 = 3.141592;
+    let deg:f64 = coord.trunc();
+    let min:f64 = coord - deg;
+    pi * (deg + 5.0 * min / 3.0) / 180.0
+}
+
+
+impl DistanceMetric {
+
+    pub fn from_name(name:&str) -> Option<DistanceMetric> {
+        // Takes a metric name such as "euclidean" or "nint" and returns the metric
+        match name.to_ascii_lowercase().as_str() {
+            "euclidean" | "euc" => Some(DistanceMetric::Euclidean),
+            "manhattan" => Some(DistanceMetric::Manhattan),
+            "chebyshev" => Some(DistanceMetric::Chebyshev),
+            "haversine" => Some(DistanceMetric::Haversine),
+            "nint" | "euc_2d" => Some(DistanceMetric::Nint),
+            "ceil" | "ceil_2d" => Some(DistanceMetric::Ceil),
+            "att" => Some(DistanceMetric::Att),
+            "geo" => Some(DistanceMetric::Geo),
+            _ => None,
+        }
+    }
+
+    pub fn distance(&self, city1:&City, city2:&City) -> f32 {
+        // Returns the distance between 2 cities under this metric
+        let xd:f64 = city1.x - city2.x;
+        let yd:f64 = city1.y - city2.y;
+        let distance:f64 = match self {
+            DistanceMetric::Euclidean => (xd * xd + yd * yd).sqrt(),
+            DistanceMetric::Manhattan => xd.abs() + yd.abs(),
+            DistanceMetric::Chebyshev => xd.abs().max(yd.abs()),
+            DistanceMetric::Haversine => {
+                let earth_radius:f64 = 6371.0;
+                let (lat1, lat2) = (city1.x.to_radians(), city2.x.to_radians());
+                let dlat:f64 = lat2 - lat1;
+                let dlon:f64 = (city2.y - city1.y).to_radians();
+                let a:f64 = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+                2.0 * earth_radius * a.sqrt().min(1.0).asin()
+            },
+            DistanceMetric::Nint => nint((xd * xd + yd * yd).sqrt()),
+            DistanceMetric::Ceil => (xd * xd + yd * yd).sqrt().ceil(),
+            DistanceMetric::Att => {
+                let rij:f64 = ((xd * xd + yd * yd) / 10.0).sqrt();
+                let tij:f64 = nint(rij);
+                if tij < rij {tij + 1.0} else {tij}
+            },
+            DistanceMetric::Geo => {
+                let rrr:f64 = 6378.388;
+                let (lat_i, lon_i) = (geo_radians(city1.x), geo_radians(city1.y));
+                let (lat_j, lon_j) = (geo_radians(city2.x), geo_radians(city2.y));
+                let q1:f64 = (lon_i - lon_j).cos();
+                let q2:f64 = (lat_i - lat_j).cos();
+                let q3:f64 = (lat_i + lat_j).cos();
+                (rrr * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos() + 1.0).trunc()
+            },
+        };
+        distance as f32
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn city(x:f64, y:f64) -> City {
+        City{name:1, x, y}
+    }
+
+    #[test]
+    fn planar_metrics() {
+        let (a, b) = (city(0.0, 0.0), city(3.0, -4.0));
+        assert_eq!(DistanceMetric::Euclidean.distance(&a, &b), 5.0);
+        assert_eq!(DistanceMetric::Manhattan.distance(&a, &b), 7.0);
+        assert_eq!(DistanceMetric::Chebyshev.distance(&a, &b), 4.0);
+        assert_eq!(DistanceMetric::Manhattan.distance(&b, &a), 7.0);
+    }
+
+    #[test]
+    fn haversine_is_great_circle_km() {
+        // x is the latitude and y the longitude, London to Paris is about 343.6 km
+        let (london, paris) = (city(51.5074, -0.1278), city(48.8566, 2.3522));
+        assert!((DistanceMetric::Haversine.distance(&london, &paris) - 343.56).abs() < 0.01);
+        // Half way round the equator is pi times the radius
+        let half_way:f32 = DistanceMetric::Haversine.distance(&city(0.0, 0.0), &city(0.0, 180.0));
+        assert!((half_way - 20015.09).abs() < 0.01);
+        assert_eq!(DistanceMetric::Haversine.distance(&london, &london), 0.0);
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        assert_eq!(DistanceMetric::from_name("Manhattan"), Some(DistanceMetric::Manhattan));
+        assert_eq!(DistanceMetric::from_name("EUC_2D"), Some(DistanceMetric::Nint));
+        assert_eq!(DistanceMetric::from_name("taxicab"), None);
+    }
+}
@@ -0,0 +1,389 @@
+use crate::ant3::{GraphMatrix, Ants, Trail, check_square, is_symmetric, get_tour_distance_graph, applicable_local_search, ants_this_iteration};
+use crate::candidates::CandidateLists;
+use crate::choice::ChoiceInfo;
+use crate::city::City;
+use crate::config::{AcoConfig, ConfigError, check_min, check_positive};
+use crate::error::AcoError;
+use crate::local_search::LocalSearch;
+use crate::stop::{Progress, StopReason};
+use crate::strategy::DepositSchedule;
+use crate::tour::Tour;
+
+
+// Share of the pheromone range an edge needs to count towards the branching factor
+const BRANCHING_LAMBDA:f32 = 0.05;
+// The trail has converged once the branching factor is this close to the tour's own edges per city
+const CONVERGED_BRANCHING:f32 = 0.1;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MmasConfig {
+    p_best: f32,
+    deposit: DepositSchedule,
+    reinit_after: Option<usize>,
+}
+
+impl Default for MmasConfig {
+    fn default() -> Self {
+        MmasConfig{p_best:0.05, deposit:DepositSchedule::IterationBest, reinit_after:Some(50)}
+    }
+}
+
+impl MmasConfig {
+
+    pub fn builder() -> MmasConfigBuilder {
+        MmasConfigBuilder::default()
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_positive("p_best", self.p_best, 1.0 - f32::EPSILON)?;
+        self.deposit.validate()?;
+        if let Some(iterations) = self.reinit_after {
+            check_min("reinit_after", iterations, 1)?;
+        }
+        Ok(())
+    }
+
+    // Probability the best tour is rebuilt once the trail has converged, sets tau_min
+    pub fn p_best(&self) -> f32 {self.p_best}
+    // Which ant deposits pheromone every iteration
+    pub fn deposit(&self) -> DepositSchedule {self.deposit}
+    // Iterations without improvement on a converged trail before it is reset to tau_max, None never resets
+    pub fn reinit_after(&self) -> Option<usize> {self.reinit_after}
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct MmasConfigBuilder {
+    config: MmasConfig,
+}
+
+impl MmasConfigBuilder {
+
+    pub fn p_best(mut self, p_best:f32) -> Self {
+        self.config.p_best = p_best;
+        self
+    }
+
+    pub fn deposit(mut self, deposit:DepositSchedule) -> Self {
+        self.config.deposit = deposit;
+        self
+    }
+
+    pub fn reinit_after(mut self, reinit_after:Option<usize>) -> Self {
+        self.config.reinit_after = reinit_after;
+        self
+    }
+
+    pub fn build(self) -> Result<MmasConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+
+fn nearest_neighbour_tour(dist_graph:&GraphMatrix) -> Vec<usize> {
+    // Greedy tour from city 0, used to set the first pheromone bounds
+    let mut visited:Vec<bool> = vec![false; dist_graph.size()];
+    let mut tour:Vec<usize> = vec![0];
+    visited[0] = true;
+    for _ in 1..dist_graph.size() {
+        let from_city_idx:usize = *tour.last().unwrap();
+        let next_city_idx:usize = (0..dist_graph.size())
+                                  .filter(|to_city_idx| !visited[*to_city_idx])
+                                  .min_by(|a, b| dist_graph[(from_city_idx, *a)].total_cmp(&dist_graph[(from_city_idx, *b)]))
+                                  .unwrap();
+        visited[next_city_idx] = true;
+        tour.push(next_city_idx);
+    }
+    tour
+}
+
+
+pub fn pheromone_bounds(best_distance:f32, evaporation:f32, p_best:f32, num_cities:usize) -> (f32, f32) {
+    /* Returns (tau_min, tau_max) from Stutzle and Hoos, MAX-MIN Ant System (2000).
+       tau_max is the level every edge of the best tour converges to,
+       tau_min is set so the converged trail rebuilds the best tour with probability p_best */
+    let tau_max:f32 = 1.0 / (evaporation * best_distance);
+    let p_dec:f32 = p_best.powf(1.0 / num_cities as f32);
+    let avg_choices:f32 = num_cities as f32 / 2.0;
+    let tau_min:f32 = if avg_choices > 1.0 {
+        tau_max * (1.0 - p_dec) / ((avg_choices - 1.0) * p_dec)
+    } else {
+        tau_max
+    };
+    (tau_min.min(tau_max), tau_max)
+}
+
+
+pub fn branching_factor(pher_graph:&GraphMatrix, lambda:f32) -> f32 {
+    // Average number of edges per city whose pheromone is in the top (1 - lambda) of that city's range
+    let num_cities:usize = pher_graph.size();
+    let total:usize = (0..num_cities).map(|from_city_idx| {
+        let edges = || pher_graph.row(from_city_idx).enumerate().filter(|(to_city_idx, _)| *to_city_idx != from_city_idx).map(|(_, pher)| pher);
+        let min:f32 = edges().fold(f32::INFINITY, f32::min);
+        let max:f32 = edges().fold(f32::NEG_INFINITY, f32::max);
+        edges().filter(|pher| *pher >= min + lambda * (max - min)).count()
+    }).sum();
+    total as f32 / num_cities as f32
+}
+
+
+pub struct MMAS<'a> {
+    cities_list:&'a Vec<City>,
+    pher_graph:GraphMatrix,
+    dist_graph:GraphMatrix,
+    config:AcoConfig,
+    mmas_config:MmasConfig,
+    symmetric:bool,
+    tau_min:f32,
+    tau_max:f32,
+    reinitialisations:usize,
+    stop_reason:Option<StopReason>,
+    iterations_run:usize,
+    choice_info:ChoiceInfo,
+    ants:Ants,
+    local_search:Option<Box<dyn LocalSearch>>,
+}
+
+impl <'a> MMAS <'a> {
+
+    pub fn new(cities_list:&'a Vec<City>, dist_graph:GraphMatrix, config:AcoConfig, mmas_config:MmasConfig) -> Result<MMAS<'a>, AcoError> {
+        /* MAX-MIN Ant System. Ants use the random proportional rule, so config.q0() and config.tau() are not used,
+           config.alpha() is the evaporation rate. The pheromone starts at tau_max of a nearest neighbour tour */
+        if cities_list.is_empty() {
+            return Err(AcoError::EmptyInstance);
+        }
+        check_square(&dist_graph, cities_list.len())?;
+        config.validate()?;
+        // With no evaporation tau_max would be unbounded
+        check_positive("alpha", config.alpha(), 1.0)?;
+        mmas_config.validate()?;
+        let symmetric:bool = is_symmetric(&dist_graph);
+        let choice_info:ChoiceInfo = ChoiceInfo::new(&GraphMatrix::new(dist_graph.size(), 0.0), &dist_graph, config.beta());
+        let ants:Ants = Ants::new(cities_list, &dist_graph, &config)?;
+        let mut mmas = MMAS{cities_list, pher_graph:GraphMatrix::default(), dist_graph, config, mmas_config, symmetric,
+                            tau_min:0.0, tau_max:0.0, reinitialisations:0, stop_reason:None, iterations_run:0,
+                            choice_info, ants, local_search:None};
+        let nn_distance:f32 = get_tour_distance_graph(&nearest_neighbour_tour(&mmas.dist_graph), &mmas.dist_graph);
+        mmas.set_bounds(nn_distance);
+        mmas.reinitialise();
+        Ok(mmas)
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        // Why the last call to optimize stopped, None before the first call
+        self.stop_reason
+    }
+
+    pub fn iterations_run(&self) -> usize {
+        // Iterations completed by the last call to optimize
+        self.iterations_run
+    }
+
+    pub fn candidates(&self) -> Option<&CandidateLists> {
+        self.ants.candidates()
+    }
+
+    pub fn with_local_search(mut self, local_search:Box<dyn LocalSearch>) -> Self {
+        // Same rules as ACO3::with_local_search(), asymmetric instances fall back to directed or-opt
+        self.local_search = Some(local_search);
+        self
+    }
+
+    pub fn local_search_name(&self) -> String {
+        self.local_search().name()
+    }
+
+    fn local_search(&self) -> &dyn LocalSearch {
+        applicable_local_search(&self.local_search, self.symmetric)
+    }
+
+    pub fn tau_bounds(&self) -> (f32, f32) {
+        (self.tau_min, self.tau_max)
+    }
+
+    pub fn reinitialisations(&self) -> usize {
+        // Number of times the trail was reset to tau_max after converging
+        self.reinitialisations
+    }
+
+    fn set_bounds(&mut self, best_distance:f32) {
+        // Guards against zero length tours on directed instances with free edges
+        let best_distance:f32 = best_distance.max(f32::EPSILON);
+        (self.tau_min, self.tau_max) = pheromone_bounds(best_distance, self.config.alpha(), self.mmas_config.p_best(), self.cities_list.len());
+    }
+
+    fn reinitialise(&mut self) {
+        let num_cities:usize = self.cities_list.len();
+        let tau_max:f32 = self.tau_max;
+        self.pher_graph = GraphMatrix::from_fn(num_cities, |from_city_idx, to_city_idx|
+                              if from_city_idx == to_city_idx {0.0} else {tau_max});
+        self.choice_info.refresh(&self.pher_graph);
+    }
+
+    fn update_pheromone(&mut self, tour:&[usize], tour_distance:f32) {
+        // Evaporates every edge, deposits on the tour's edges and clamps the trail to [tau_min, tau_max]
+        let evaporation:f32 = self.config.alpha();
+        let deposit:f32 = 1.0 / tour_distance.max(f32::EPSILON);
+        for pher in self.pher_graph.values_mut() {
+            *pher *= 1.0 - evaporation;
+        }
+        for (from_city_idx, to_city_idx) in tour.iter().zip(tour.iter().cycle().skip(1)) {
+            self.pher_graph[(*from_city_idx, *to_city_idx)] += deposit;
+            if self.symmetric {
+                self.pher_graph[(*to_city_idx, *from_city_idx)] += deposit;
+            }
+        }
+        let (tau_min, tau_max) = (self.tau_min, self.tau_max);
+        self.pher_graph.map(|from_city_idx, to_city_idx, pher|
+            if from_city_idx == to_city_idx {pher} else {pher.clamp(tau_min, tau_max)});
+        self.choice_info.refresh(&self.pher_graph);
+    }
+
+    fn has_converged(&self) -> bool {
+        // Every city is left with only its own tour edges above tau_min
+        let tour_edges:f32 = if self.symmetric {2.0} else {1.0};
+        branching_factor(&self.pher_graph, BRANCHING_LAMBDA) < tour_edges + CONVERGED_BRANCHING
+    }
+
+    pub fn optimize(&mut self) -> Tour {
+        // At least one iteration is run so there is always a tour to return
+        let mut best_tour:Vec<usize> = Vec::new();
+        let mut best_tour_distance:f32 = f32::INFINITY;
+        let mut since_improvement:usize = 0;
+        let mut progress:Progress = Progress::new();
+        self.stop_reason = Some(StopReason::Iterations);
+
+        for i in 0..self.config.iterations() {
+
+            let num_ants:usize = match ants_this_iteration(&self.config, &progress) {
+                Ok(num_ants) => num_ants,
+                Err(reason) => {
+                    self.stop_reason = Some(reason);
+                    break;
+                },
+            };
+
+            // The random proportional rule, q0 is always 0 and there is no step update
+            let trail = Trail{pher_graph:&mut self.pher_graph, choice_info:&mut self.choice_info, step_update:None, q0:0.0};
+            let local_search:&dyn LocalSearch = applicable_local_search(&self.local_search, self.symmetric);
+            let tours:Vec<Vec<usize>> = self.ants.construct_and_improve(num_ants, trail, local_search, &self.dist_graph);
+
+            let tour_distances:Vec<f32> = tours.iter().map(|tour| get_tour_distance_graph(tour, &self.dist_graph)).collect();
+            let iteration_best:usize = (0..tours.len()).min_by(|a, b| tour_distances[*a].total_cmp(&tour_distances[*b])).unwrap();
+            if tour_distances[iteration_best] < best_tour_distance {
+                best_tour = tours[iteration_best].clone();
+                best_tour_distance = tour_distances[iteration_best];
+                self.set_bounds(best_tour_distance);
+                since_improvement = 0;
+            } else {
+                since_improvement += 1;
+            }
+
+            if self.mmas_config.deposit().uses_best_so_far(i) {
+                let tour:Vec<usize> = best_tour.clone();
+                self.update_pheromone(&tour, best_tour_distance);
+            } else {
+                self.update_pheromone(&tours[iteration_best], tour_distances[iteration_best]);
+            }
+
+            if self.mmas_config.reinit_after().is_some_and(|after| since_improvement >= after) && self.has_converged() {
+                self.reinitialise();
+                self.reinitialisations += 1;
+                since_improvement = 0;
+            }
+            progress.record_iteration(num_ants, best_tour_distance);
+        }
+        self.iterations_run = progress.iterations();
+        Tour::new(best_tour, self.cities_list.len()).expect("every ant builds a permutation of the cities")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::ant3::get_distance_graph;
+    use crate::metric::DistanceMetric;
+
+    use super::*;
+
+    fn scattered_cities(num_cities:usize) -> Vec<City> {
+        (0..num_cities).map(|i| City{name:(i + 1) as i32, x:(i * 37 % 101) as f64, y:(i * 59 % 97) as f64}).collect()
+    }
+
+    fn within_bounds(mmas:&MMAS) -> bool {
+        let (tau_min, tau_max) = mmas.tau_bounds();
+        let num_cities:usize = mmas.pher_graph.size();
+        (0..num_cities).all(|from_city_idx| (0..num_cities)
+            .all(|to_city_idx| from_city_idx == to_city_idx || (tau_min..=tau_max).contains(&mmas.pher_graph[(from_city_idx, to_city_idx)])))
+    }
+
+    #[test]
+    fn bounds_are_ordered() {
+        let (tau_min, tau_max) = pheromone_bounds(100.0, 0.02, 0.05, 50);
+        assert!((tau_max - 0.5).abs() < 1e-6);
+        assert!(tau_min > 0.0 && tau_min < tau_max);
+        // With two cities there is no choice left to make
+        assert_eq!(pheromone_bounds(100.0, 0.02, 0.05, 2), (0.5, 0.5));
+    }
+
+    #[test]
+    fn pheromone_is_clamped_to_the_bounds() {
+        let cities:Vec<City> = scattered_cities(12);
+        let config:AcoConfig = AcoConfig::builder().alpha(0.5).seed(1).build().unwrap();
+        let mut mmas = MMAS::new(&cities, get_distance_graph(&cities, DistanceMetric::Euclidean), config, MmasConfig::default()).unwrap();
+        let tour:Vec<usize> = (0..cities.len()).collect();
+        // A very short tour deposits far above tau_max, repeated evaporation pushes the rest below tau_min
+        for _ in 0..50 {
+            mmas.update_pheromone(&tour, 1e-3);
+            assert!(within_bounds(&mmas));
+        }
+        let (tau_min, tau_max) = mmas.tau_bounds();
+        assert_eq!(mmas.choice_info, ChoiceInfo::new(&mmas.pher_graph, &mmas.dist_graph, mmas.config.beta()));
+        assert_eq!(mmas.pher_graph[(0, 1)], tau_max);
+        assert_eq!(mmas.pher_graph[(0, 2)], tau_min);
+        assert!(mmas.has_converged());
+    }
+
+    #[test]
+    fn reinitialising_resets_the_trail_to_tau_max() {
+        let cities:Vec<City> = scattered_cities(12);
+        let config:AcoConfig = AcoConfig::builder().alpha(0.5).seed(1).build().unwrap();
+        let mut mmas = MMAS::new(&cities, get_distance_graph(&cities, DistanceMetric::Euclidean), config, MmasConfig::default()).unwrap();
+        let tour:Vec<usize> = (0..cities.len()).collect();
+        for _ in 0..50 {
+            mmas.update_pheromone(&tour, 1e-3);
+        }
+        mmas.reinitialise();
+        assert_eq!(mmas.choice_info, ChoiceInfo::new(&mmas.pher_graph, &mmas.dist_graph, mmas.config.beta()));
+        let (_, tau_max) = mmas.tau_bounds();
+        assert!((0..cities.len()).all(|from_city_idx| (0..cities.len())
+            .all(|to_city_idx| mmas.pher_graph[(from_city_idx, to_city_idx)] == if from_city_idx == to_city_idx {0.0} else {tau_max})));
+        assert!(!mmas.has_converged());
+    }
+
+    #[test]
+    fn a_stagnating_run_reinitialises() {
+        let cities:Vec<City> = scattered_cities(10);
+        let config:AcoConfig = AcoConfig::builder().alpha(0.5).iterations(200).num_ants(5).seed(3).build().unwrap();
+        let mmas_config:MmasConfig = MmasConfig::builder().reinit_after(Some(5)).build().unwrap();
+        let mut mmas = MMAS::new(&cities, get_distance_graph(&cities, DistanceMetric::Euclidean), config, mmas_config).unwrap();
+        mmas.optimize();
+        assert!(mmas.reinitialisations() > 0);
+        assert!(within_bounds(&mmas));
+    }
+
+    #[test]
+    fn invalid_mmas_config_is_rejected() {
+        assert!(MmasConfig::builder().p_best(0.0).build().is_err());
+        assert!(MmasConfig::builder().p_best(1.0).build().is_err());
+        assert!(MmasConfig::builder().deposit(DepositSchedule::Schedule(0)).build().is_err());
+        assert!(MmasConfig::builder().reinit_after(Some(0)).build().is_err());
+        assert!(MmasConfig::builder().reinit_after(None).build().is_ok());
+    }
+}
@@ -0,0 +1,77 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub type AntRng = ChaCha8Rng;
+
+
+pub fn colony_rng(seed:Option<u64>) -> AntRng {
+    // Returns a seeded generator, or one seeded from the OS when no seed is given
+    match seed {
+        Some(seed) => AntRng::seed_from_u64(seed),
+        None => AntRng::from_entropy(),
+    }
+}
+
+
+pub fn ant_rngs(colony_rng:&mut AntRng, num_ants:usize) -> Vec<AntRng> {
+    // Draws one seed per ant from the colony generator, so every ant gets its own
+    // stream and the tours do not depend on the order the ants are run in
+    (0..num_ants).map(|_| AntRng::seed_from_u64(colony_rng.gen())).collect()
+}
+
+
+pub fn roulette_wheel<R: Rng>(scores:&[f32], rng:&mut R) -> usize {
+    // Returns an index with probability proportional to its score
+    // Falls back to the best score if the scores cannot be normalised
+    let total:f32 = scores.iter().sum();
+    if total.is_nan() || total <= 0.0 || total.is_infinite() {
+        let mut max_index:usize = 0;
+        for (i, score) in scores.iter().enumerate() {
+            if *score > scores[max_index] {
+                max_index = i;
+            }
+        }
+        return max_index;
+    }
+    let mut target:f32 = rng.gen::<f32>() * total;
+    let mut last_positive:usize = 0;
+    for (i, score) in scores.iter().enumerate() {
+        if *score > 0.0 {
+            if target < *score {
+                return i;
+            }
+            target -= score;
+            last_positive = i;
+        }
+    }
+    // Rounding can leave a little of the target over, that belongs to the last city
+    last_positive
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seed_gives_the_same_ant_streams() {
+        let mut first:AntRng = colony_rng(Some(3));
+        let mut second:AntRng = colony_rng(Some(3));
+        let draws = |rngs:Vec<AntRng>| rngs.into_iter().map(|mut rng| rng.gen::<u64>()).collect::<Vec<u64>>();
+        let first_draws:Vec<u64> = draws(ant_rngs(&mut first, 4));
+        assert_eq!(first_draws, draws(ant_rngs(&mut second, 4)));
+        // Every ant gets its own stream
+        assert!(first_draws.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn roulette_wheel_skips_zero_scores() {
+        let mut rng:AntRng = colony_rng(Some(1));
+        for _ in 0..100 {
+            assert_eq!(roulette_wheel(&[0.0, 2.0, 0.0], &mut rng), 1);
+        }
+        // Scores that cannot be normalised fall back to the best one
+        assert_eq!(roulette_wheel(&[0.0, 0.0, 0.0], &mut rng), 0);
+        assert_eq!(roulette_wheel(&[1.0, f32::INFINITY, 2.0], &mut rng), 1);
+    }
+}
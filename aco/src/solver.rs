@@ -0,0 +1,244 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::aco::ACO;
+use crate::ant3::{ACO3, GraphMatrix, check_square, get_pheromone_graph};
+use crate::ant_colony::AntColony;
+use crate::city::City;
+use crate::config::AcoConfig;
+use crate::error::AcoError;
+use crate::graph::{Graph, create_pheromone_graph, create_distance_graph};
+use crate::local_search::LocalSearch;
+use crate::metric::DistanceMetric;
+use crate::mmas::{MMAS, MmasConfig};
+use crate::stop::StopReason;
+use crate::strategy::{PheromoneStrategy, AntColonySystem, DepositSchedule};
+use crate::tour::Tour;
+use crate::tsplib::TspInstance;
+
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub cities: Vec<City>,
+    pub dist_graph: GraphMatrix,
+    // The metric dist_graph was built with, None for explicit matrices
+    pub metric: Option<DistanceMetric>,
+}
+
+impl Instance {
+
+    pub fn new(cities:Vec<City>, dist_graph:GraphMatrix, metric:Option<DistanceMetric>) -> Result<Instance, AcoError> {
+        if cities.is_empty() {
+            return Err(AcoError::EmptyInstance);
+        }
+        check_square(&dist_graph, cities.len())?;
+        Ok(Instance{cities, dist_graph, metric})
+    }
+
+    pub fn num_cities(&self) -> usize {
+        self.cities.len()
+    }
+}
+
+impl From<TspInstance> for Instance {
+    fn from(instance:TspInstance) -> Self {
+        Instance{cities:instance.cities, dist_graph:instance.dist_graph, metric:instance.metric}
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveResult {
+    pub tour: Tour,
+    pub distance: f32,
+    pub iterations: usize,
+    pub elapsed: Duration,
+    pub stop_reason: StopReason,
+    // Pheromone rule and local search of the acs solver
+    pub strategy: Option<&'static str>,
+    pub local_search: Option<String>,
+    // Trail resets of the mmas solver
+    pub reinitialisations: Option<usize>,
+}
+
+impl SolveResult {
+
+    fn new(instance:&Instance, tour:Tour, iterations:usize, started:Instant, stop_reason:Option<StopReason>) -> SolveResult {
+        let distance:f32 = tour.distance(&instance.dist_graph);
+        SolveResult{tour, distance, iterations, elapsed:started.elapsed(), stop_reason:stop_reason.unwrap_or(StopReason::Iterations),
+                    strategy:None, local_search:None, reinitialisations:None}
+    }
+}
+
+
+pub trait Solver {
+
+    fn name(&self) -> &'static str;
+
+    // Runs the optimiser once on instance, the solver is consumed as it owns its strategy and local search
+    fn solve(self: Box<Self>, instance:&Instance, config:AcoConfig) -> Result<SolveResult, AcoError>;
+}
+
+
+pub struct Aco3Solver {
+    pub strategy: Box<dyn PheromoneStrategy>,
+    pub local_search: Option<Box<dyn LocalSearch>>,
+    // Stops once a tour as short as this one is found
+    pub reference: Option<Tour>,
+}
+
+impl Default for Aco3Solver {
+    fn default() -> Self {
+        Aco3Solver{strategy:Box::new(AntColonySystem::default()), local_search:None, reference:None}
+    }
+}
+
+impl Solver for Aco3Solver {
+
+    fn name(&self) -> &'static str {"acs"}
+
+    fn solve(self: Box<Self>, instance:&Instance, config:AcoConfig) -> Result<SolveResult, AcoError> {
+        let started:Instant = Instant::now();
+        let pher_graph:GraphMatrix = get_pheromone_graph(&instance.cities, config.tau());
+        let mut colony = ACO3::new(&instance.cities, pher_graph, instance.dist_graph.clone(), config, self.reference)?
+                         .with_strategy(self.strategy);
+        if let Some(local_search) = self.local_search {
+            colony = colony.with_local_search(local_search);
+        }
+        let tour:Tour = colony.optimize();
+        let mut result:SolveResult = SolveResult::new(instance, tour, colony.iterations_run(), started, colony.stop_reason());
+        result.strategy = Some(colony.strategy_name());
+        result.local_search = Some(colony.local_search_name());
+        Ok(result)
+    }
+}
+
+
+#[derive(Default)]
+pub struct MmasSolver {
+    pub mmas_config: MmasConfig,
+    pub local_search: Option<Box<dyn LocalSearch>>,
+}
+
+impl Solver for MmasSolver {
+
+    fn name(&self) -> &'static str {"mmas"}
+
+    fn solve(self: Box<Self>, instance:&Instance, config:AcoConfig) -> Result<SolveResult, AcoError> {
+        let started:Instant = Instant::now();
+        let mut mmas = MMAS::new(&instance.cities, instance.dist_graph.clone(), config, self.mmas_config)?;
+        if let Some(local_search) = self.local_search {
+            mmas = mmas.with_local_search(local_search);
+        }
+        let tour:Tour = mmas.optimize();
+        let mut result:SolveResult = SolveResult::new(instance, tour, mmas.iterations_run(), started, mmas.stop_reason());
+        result.local_search = Some(mmas.local_search_name());
+        result.reinitialisations = Some(mmas.reinitialisations());
+        Ok(result)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AntColonySolver;
+
+impl Solver for AntColonySolver {
+
+    fn name(&self) -> &'static str {"as"}
+
+    fn solve(self: Box<Self>, instance:&Instance, config:AcoConfig) -> Result<SolveResult, AcoError> {
+        let started:Instant = Instant::now();
+        let mut colony = AntColony::new(&instance.cities, instance.dist_graph.clone(), config)?;
+        let tour:Tour = colony.optimize();
+        Ok(SolveResult::new(instance, tour, colony.iterations_run(), started, colony.stop_reason()))
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct AcoSolver {
+    pub deposit: DepositSchedule,
+    // Stops once a tour as short as this one is found
+    pub reference: Option<Tour>,
+}
+
+impl Default for AcoSolver {
+    fn default() -> Self {
+        AcoSolver{deposit:DepositSchedule::BestSoFar, reference:None}
+    }
+}
+
+impl Solver for AcoSolver {
+
+    fn name(&self) -> &'static str {"aco"}
+
+    fn solve(self: Box<Self>, instance:&Instance, config:AcoConfig) -> Result<SolveResult, AcoError> {
+        // ACO works on the HashMap graph, which is built from coordinates
+        let started:Instant = Instant::now();
+        let metric:DistanceMetric = instance.metric.ok_or_else(|| AcoError::InvalidInstance{
+            message:"the aco solver needs city coordinates".to_string()})?;
+        let pheromone_graph:Arc<Mutex<Graph>> = create_pheromone_graph(&instance.cities, config.tau());
+        let distance_graph:Graph = create_distance_graph(&instance.cities, metric);
+        let short_path:Vec<&City> = self.reference.iter().flat_map(|tour| tour.cities()).map(|city_idx| &instance.cities[*city_idx]).collect();
+        let parallel:bool = config.parallel();
+        let mut aco = ACO::new(instance.cities.clone(), pheromone_graph, distance_graph, config)?.with_deposit(self.deposit)?;
+        if parallel {
+            aco.optimize_concurrent_rayon(short_path);
+        } else {
+            aco.optimize(short_path);
+        }
+        if aco.best_path.is_empty() {
+            return Err(AcoError::NoTour);
+        }
+        // City names are their 1-based position in the instance
+        let tour:Tour = Tour::new(aco.best_path.iter().map(|city| (city.name - 1) as usize).collect(), instance.num_cities())?;
+        Ok(SolveResult::new(instance, tour, aco.iterations_run(), started, aco.stop_reason()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::ant3::get_distance_graph;
+    use crate::rng::{AntRng, colony_rng};
+
+    fn random_instance(num_cities:usize) -> Instance {
+        let mut rng:AntRng = colony_rng(Some(11));
+        let cities:Vec<City> = (0..num_cities).map(|i| City{name:(i + 1) as i32, x:rng.gen_range(0.0..100.0), y:rng.gen_range(0.0..100.0)}).collect();
+        let dist_graph:GraphMatrix = get_distance_graph(&cities, DistanceMetric::Euclidean);
+        Instance::new(cities, dist_graph, Some(DistanceMetric::Euclidean)).unwrap()
+    }
+
+    fn solvers() -> Vec<Box<dyn Fn() -> Box<dyn Solver>>> {
+        vec![Box::new(|| Box::new(Aco3Solver::default())), Box::new(|| Box::new(MmasSolver::default())),
+             Box::new(|| Box::new(AntColonySolver)), Box::new(|| Box::new(AcoSolver::default()))]
+    }
+
+    fn seeded(seed:u64, parallel:bool) -> AcoConfig {
+        AcoConfig::builder().iterations(5).num_ants(8).seed(seed).parallel(parallel).threads(2).build().unwrap()
+    }
+
+    #[test]
+    fn a_seed_reproduces_the_same_tour() {
+        let instance:Instance = random_instance(20);
+        for solver in solvers() {
+            let first:SolveResult = solver().solve(&instance, seeded(5, false)).unwrap();
+            let second:SolveResult = solver().solve(&instance, seeded(5, false)).unwrap();
+            assert_eq!(first.tour, second.tour, "{} gave two tours for one seed", solver().name());
+            assert_eq!(first.distance, second.distance);
+        }
+    }
+
+    #[test]
+    fn parallel_runs_build_the_same_tour() {
+        let instance:Instance = random_instance(20);
+        for solver in solvers() {
+            let sequential:SolveResult = solver().solve(&instance, seeded(9, false)).unwrap();
+            let parallel:SolveResult = solver().solve(&instance, seeded(9, true)).unwrap();
+            assert_eq!(sequential.tour, parallel.tour, "{} depends on the thread pool", solver().name());
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Iterations,
+    TimeLimit,
+    MaxEvaluations,
+    Stagnation,
+    TargetReached,
+    Cancelled,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let reason:&str = match self {
+            StopReason::Iterations => "iteration limit reached",
+            StopReason::TimeLimit => "time limit reached",
+            StopReason::MaxEvaluations => "evaluation limit reached",
+            StopReason::Stagnation => "no improvement",
+            StopReason::TargetReached => "target distance reached",
+            StopReason::Cancelled => "cancelled",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct StopCriteria {
+    pub(crate) time_limit: Option<Duration>,
+    pub(crate) max_evaluations: Option<usize>,
+    pub(crate) stagnation: Option<usize>,
+    pub(crate) target_distance: Option<f32>,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+}
+
+impl PartialEq for StopCriteria {
+    fn eq(&self, other:&Self) -> bool {
+        // Cancellation flags are equal when they are the same flag
+        let same_cancel:bool = match (&self.cancel, &other.cancel) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.time_limit == other.time_limit && self.max_evaluations == other.max_evaluations &&
+        self.stagnation == other.stagnation && self.target_distance == other.target_distance && same_cancel
+    }
+}
+
+impl StopCriteria {
+
+    // Wall-clock budget for a run
+    pub fn time_limit(&self) -> Option<Duration> {self.time_limit}
+    // Number of tours that may be built
+    pub fn max_evaluations(&self) -> Option<usize> {self.max_evaluations}
+    // Number of iterations in a row without a better tour before giving up
+    pub fn stagnation(&self) -> Option<usize> {self.stagnation}
+    // The run stops once a tour at least this short is found
+    pub fn target_distance(&self) -> Option<f32> {self.target_distance}
+    // Flag another thread can set to stop the run
+    pub fn cancel_flag(&self) -> Option<&Arc<AtomicBool>> {self.cancel.as_ref()}
+
+    pub fn check(&self, progress:&Progress) -> Option<StopReason> {
+        /* Returns the first criterion that has fired, checked in the order
+           cancelled, target, time limit, evaluations, stagnation */
+        if self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            return Some(StopReason::Cancelled);
+        }
+        if self.target_distance.is_some_and(|target| progress.best_distance <= target) {
+            return Some(StopReason::TargetReached);
+        }
+        if self.time_limit.is_some_and(|limit| progress.start_time.elapsed() >= limit) {
+            return Some(StopReason::TimeLimit);
+        }
+        if self.max_evaluations.is_some_and(|max| progress.evaluations >= max) {
+            return Some(StopReason::MaxEvaluations);
+        }
+        if self.stagnation.is_some_and(|max| progress.stagnant_iterations >= max) {
+            return Some(StopReason::Stagnation);
+        }
+        None
+    }
+
+    pub fn check_before_iteration(&self, progress:&Progress) -> Option<StopReason> {
+        // Nothing stops the first iteration, so every solver has a tour to return
+        if progress.iterations == 0 {None} else {self.check(progress)}
+    }
+
+    pub fn ants_this_iteration(&self, progress:&Progress, num_ants:usize) -> usize {
+        // Fewer ants than num_ants when a full iteration would go over the evaluation budget
+        self.max_evaluations.map_or(num_ants, |max| num_ants.min(max.saturating_sub(progress.evaluations)))
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Progress {
+    start_time: Instant,
+    iterations: usize,
+    evaluations: usize,
+    stagnant_iterations: usize,
+    best_distance: f32,
+}
+
+impl Progress {
+
+    pub fn new() -> Progress {
+        Progress{start_time:Instant::now(), iterations:0, evaluations:0, stagnant_iterations:0, best_distance:f32::INFINITY}
+    }
+
+    pub fn record_iteration(&mut self, tours_built:usize, best_distance:f32) {
+        // Called once per iteration with the number of tours built and the best distance so far
+        self.iterations += 1;
+        self.evaluations += tours_built;
+        if best_distance < self.best_distance {
+            self.best_distance = best_distance;
+            self.stagnant_iterations = 0;
+        } else {
+            self.stagnant_iterations += 1;
+        }
+    }
+
+    // Iterations recorded since the start of the run
+    pub fn iterations(&self) -> usize {self.iterations}
+    // Tours built since the start of the run
+    pub fn evaluations(&self) -> usize {self.evaluations}
+    // Time since the start of the run
+    pub fn elapsed(&self) -> Duration {self.start_time.elapsed()}
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_fires_without_criteria() {
+        let mut progress:Progress = Progress::new();
+        progress.record_iteration(1000, 1.0);
+        assert_eq!(StopCriteria::default().check(&progress), None);
+    }
+
+    #[test]
+    fn evaluations_count_every_tour_built() {
+        let criteria = StopCriteria{max_evaluations:Some(20), ..Default::default()};
+        let mut progress:Progress = Progress::new();
+        progress.record_iteration(10, 5.0);
+        assert_eq!(criteria.check(&progress), None);
+        progress.record_iteration(10, 5.0);
+        assert_eq!(progress.evaluations(), 20);
+        assert_eq!(criteria.check(&progress), Some(StopReason::MaxEvaluations));
+    }
+
+    #[test]
+    fn stagnation_resets_on_improvement() {
+        let criteria = StopCriteria{stagnation:Some(2), ..Default::default()};
+        let mut progress:Progress = Progress::new();
+        for best_distance in [9.0, 9.0, 8.0, 8.0] {
+            progress.record_iteration(1, best_distance);
+            assert_eq!(criteria.check(&progress), None);
+        }
+        progress.record_iteration(1, 8.0);
+        assert_eq!(criteria.check(&progress), Some(StopReason::Stagnation));
+    }
+
+    #[test]
+    fn target_and_time_limit() {
+        let mut progress:Progress = Progress::new();
+        progress.record_iteration(1, 10.0);
+        assert_eq!(StopCriteria{target_distance:Some(9.5), ..Default::default()}.check(&progress), None);
+        assert_eq!(StopCriteria{target_distance:Some(10.0), ..Default::default()}.check(&progress), Some(StopReason::TargetReached));
+        assert_eq!(StopCriteria{time_limit:Some(Duration::ZERO), ..Default::default()}.check(&progress), Some(StopReason::TimeLimit));
+        assert_eq!(StopCriteria{time_limit:Some(Duration::from_secs(3600)), ..Default::default()}.check(&progress), None);
+    }
+
+    #[test]
+    fn cancellation_is_checked_first() {
+        let cancel:Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let criteria = StopCriteria{cancel:Some(Arc::clone(&cancel)), time_limit:Some(Duration::ZERO), ..Default::default()};
+        let progress:Progress = Progress::new();
+        assert_eq!(criteria.check(&progress), Some(StopReason::TimeLimit));
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(criteria.check(&progress), Some(StopReason::Cancelled));
+        assert_eq!(StopReason::Cancelled.to_string(), "cancelled");
+    }
+
+    #[test]
+    fn the_first_iteration_always_runs() {
+        let criteria = StopCriteria{time_limit:Some(Duration::ZERO), ..Default::default()};
+        let mut progress:Progress = Progress::new();
+        assert_eq!(criteria.check_before_iteration(&progress), None);
+        progress.record_iteration(1, 5.0);
+        assert_eq!(criteria.check_before_iteration(&progress), Some(StopReason::TimeLimit));
+    }
+
+    #[test]
+    fn the_last_iteration_stops_at_the_evaluation_budget() {
+        let criteria = StopCriteria{max_evaluations:Some(15), ..Default::default()};
+        let mut progress:Progress = Progress::new();
+        assert_eq!(criteria.ants_this_iteration(&progress, 10), 10);
+        progress.record_iteration(10, 5.0);
+        assert_eq!(criteria.ants_this_iteration(&progress, 10), 5);
+        progress.record_iteration(5, 5.0);
+        assert_eq!(criteria.check_before_iteration(&progress), Some(StopReason::MaxEvaluations));
+        assert_eq!(StopCriteria::default().ants_this_iteration(&progress, 10), 10);
+    }
+}
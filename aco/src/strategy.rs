@@ -0,0 +1,422 @@
+use crate::ant3::{GraphMatrix, local_pheromone_update3, global_pheromone_update};
+use crate::choice::ChoiceInfo;
+use crate::city::City;
+use crate::config::{AcoConfig, ConfigError, check_range, check_min};
+
+
+pub struct UpdateContext<'c> {
+    pub cities_list: &'c [City],
+    pub config: &'c AcoConfig,
+    pub symmetric: bool,
+}
+
+
+pub struct IterationTours<'t> {
+    pub iteration: usize,
+    pub tours: &'t [Vec<usize>],
+    pub distances: &'t [f32],
+    pub best_tour: &'t [usize],
+    pub best_distance: f32,
+}
+
+impl IterationTours<'_> {
+
+    pub fn iteration_best(&self) -> (&[usize], f32) {
+        // The shortest tour built this iteration, the first one on ties
+        let best:usize = (0..self.tours.len()).min_by(|a, b| self.distances[*a].total_cmp(&self.distances[*b])).unwrap_or(0);
+        match self.tours.get(best) {
+            Some(tour) => (tour, self.distances[best]),
+            None => (self.best_tour, self.best_distance),
+        }
+    }
+
+    pub fn depositing(&self, schedule:DepositSchedule) -> (&[usize], f32) {
+        // The tour that deposits this iteration under schedule
+        if schedule.uses_best_so_far(self.iteration) {
+            (self.best_tour, self.best_distance)
+        } else {
+            self.iteration_best()
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositSchedule {
+    IterationBest,
+    BestSoFar,
+    // The best-so-far tour deposits every n'th iteration, the iteration-best tour otherwise
+    Schedule(usize),
+}
+
+impl DepositSchedule {
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // Schedule(0) would never let the best-so-far tour deposit
+        if let DepositSchedule::Schedule(every) = self {
+            check_min("deposit schedule", *every, 1)?;
+        }
+        Ok(())
+    }
+
+    pub fn uses_best_so_far(&self, iteration:usize) -> bool {
+        // iteration counts from 0, so Schedule(n) picks the best-so-far tour on iterations n - 1, 2n - 1, ...
+        match self {
+            DepositSchedule::IterationBest => false,
+            DepositSchedule::BestSoFar => true,
+            DepositSchedule::Schedule(every) => (iteration + 1).is_multiple_of(*every),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepUpdate {
+    pub rho: f32,
+    pub tau: f32,
+    pub symmetric: bool,
+}
+
+impl StepUpdate {
+
+    pub fn apply(&self, pher_graph:&mut GraphMatrix, choice_info:&mut ChoiceInfo, from_city_idx:usize, to_city_idx:usize) {
+        // Decays the edge an ant just crossed towards tau, and the reverse edge on symmetric instances
+        let reverse = Some((to_city_idx, from_city_idx)).filter(|_| self.symmetric);
+        for (i, j) in std::iter::once((from_city_idx, to_city_idx)).chain(reverse) {
+            pher_graph[(i, j)] = (1.0 - self.rho) * pher_graph[(i, j)] + self.rho * self.tau;
+            choice_info.refresh_edge(pher_graph, i, j);
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalUpdate {
+    // The ACS rule, each step decays the edge the ant took and later ants see the change
+    #[default]
+    PerStep,
+    // Decays every edge leaving every city of each finished tour, reproduces results from before PerStep
+    EndOfTour,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlobalUpdate {
+    // The ACS rule, only the depositing tour's edges decay by alpha and receive alpha / L
+    #[default]
+    BestTourEdges,
+    // Every edge evaporates by alpha, then the depositing tour's edges receive alpha / L
+    EvaporateAll,
+    // Decays every edge leaving every city of the best-so-far tour, reproduces results from before BestTourEdges
+    AllEdgesFromTour,
+}
+
+
+pub trait PheromoneStrategy {
+
+    fn name(&self) -> &'static str;
+
+    fn q0(&self, _config:&AcoConfig) -> f32 {
+        // Probability of taking the best scored city, the Ant System family always samples
+        0.0
+    }
+
+    fn step_update(&self, _context:&UpdateContext) -> Option<StepUpdate> {
+        // Some makes the ants build their tours one after another, applying the update after every step
+        None
+    }
+
+    fn local_update(&self, _pher_graph:&mut GraphMatrix, _tour:&[usize], _context:&UpdateContext) {
+        // Called after every ant's tour, only the Ant Colony System updates here
+    }
+
+    // Called once per iteration after every ant has built its tour
+    fn global_update(&self, pher_graph:&mut GraphMatrix, iteration:&IterationTours, context:&UpdateContext);
+}
+
+
+fn evaporate(pher_graph:&mut GraphMatrix, evaporation:f32) {
+    for pher in pher_graph.values_mut() {
+        *pher *= 1.0 - evaporation;
+    }
+}
+
+
+fn tour_edges(tour:&[usize], symmetric:bool) -> impl Iterator<Item = (usize, usize)> + '_ {
+    // Every edge of the closed tour, followed by its reverse on symmetric instances
+    tour.iter().zip(tour.iter().cycle().skip(1))
+        .flat_map(move |(from_city_idx, to_city_idx)| std::iter::once((*from_city_idx, *to_city_idx))
+                  .chain(Some((*to_city_idx, *from_city_idx)).filter(|_| symmetric)))
+}
+
+
+fn deposit(pher_graph:&mut GraphMatrix, tour:&[usize], amount:f32, symmetric:bool) {
+    // Adds amount to every edge of the closed tour, and to the reverse edges on symmetric instances
+    for (from_city_idx, to_city_idx) in tour_edges(tour, symmetric) {
+        pher_graph[(from_city_idx, to_city_idx)] += amount;
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct AntColonySystem {
+    local_update: LocalUpdate,
+    global_update: GlobalUpdate,
+    deposit: DepositSchedule,
+}
+
+impl AntColonySystem {
+    pub fn new(local_update:LocalUpdate, global_update:GlobalUpdate, deposit:DepositSchedule) -> Result<Self, ConfigError> {
+        deposit.validate()?;
+        Ok(AntColonySystem{local_update, global_update, deposit})
+    }
+}
+
+impl Default for AntColonySystem {
+    fn default() -> Self {
+        AntColonySystem{local_update:LocalUpdate::default(), global_update:GlobalUpdate::default(), deposit:DepositSchedule::BestSoFar}
+    }
+}
+
+impl PheromoneStrategy for AntColonySystem {
+
+    fn name(&self) -> &'static str {"acs"}
+
+    fn q0(&self, config:&AcoConfig) -> f32 {
+        config.q0()
+    }
+
+    fn step_update(&self, context:&UpdateContext) -> Option<StepUpdate> {
+        match self.local_update {
+            LocalUpdate::PerStep => Some(StepUpdate{rho:context.config.rho(), tau:context.config.tau(), symmetric:context.symmetric}),
+            LocalUpdate::EndOfTour => None,
+        }
+    }
+
+    fn local_update(&self, pher_graph:&mut GraphMatrix, tour:&[usize], context:&UpdateContext) {
+        if self.local_update == LocalUpdate::EndOfTour {
+            let (rho, tau) = (context.config.rho(), context.config.tau());
+            local_pheromone_update3(pher_graph, tour, context.cities_list, rho, tau);
+        }
+    }
+
+    fn global_update(&self, pher_graph:&mut GraphMatrix, iteration:&IterationTours, context:&UpdateContext) {
+        let alpha:f32 = context.config.alpha();
+        let (tour, distance) = iteration.depositing(self.deposit);
+        let amount:f32 = alpha / distance.max(f32::EPSILON);
+        match self.global_update {
+            GlobalUpdate::BestTourEdges => {
+                for (from_city_idx, to_city_idx) in tour_edges(tour, context.symmetric) {
+                    pher_graph[(from_city_idx, to_city_idx)] = (1.0 - alpha) * pher_graph[(from_city_idx, to_city_idx)] + amount;
+                }
+            },
+            GlobalUpdate::EvaporateAll => {
+                evaporate(pher_graph, alpha);
+                deposit(pher_graph, tour, amount, context.symmetric);
+            },
+            GlobalUpdate::AllEdgesFromTour => {
+                global_pheromone_update(pher_graph, iteration.best_tour, context.cities_list, alpha, iteration.best_distance);
+            },
+        }
+    }
+}
+
+
+pub struct AntSystem;
+
+impl PheromoneStrategy for AntSystem {
+
+    fn name(&self) -> &'static str {"as"}
+
+    fn global_update(&self, pher_graph:&mut GraphMatrix, iteration:&IterationTours, context:&UpdateContext) {
+        // Every ant deposits 1 / L on its tour after config.alpha() of the trail has evaporated
+        evaporate(pher_graph, context.config.alpha());
+        for (tour, distance) in iteration.tours.iter().zip(iteration.distances) {
+            deposit(pher_graph, tour, 1.0 / distance.max(f32::EPSILON), context.symmetric);
+        }
+    }
+}
+
+
+pub struct ElitistAntSystem {
+    // Weight of the best-so-far tour's extra deposit, None uses the number of cities
+    elitist_weight: Option<f32>,
+}
+
+impl ElitistAntSystem {
+    pub fn new(elitist_weight:Option<f32>) -> Result<Self, ConfigError> {
+        // A negative weight would take pheromone off the best tour, NaN would spread through the whole graph
+        if let Some(weight) = elitist_weight {
+            check_range("elitist_weight", weight, 0.0, f32::MAX)?;
+        }
+        Ok(ElitistAntSystem{elitist_weight})
+    }
+}
+
+impl PheromoneStrategy for ElitistAntSystem {
+
+    fn name(&self) -> &'static str {"eas"}
+
+    fn global_update(&self, pher_graph:&mut GraphMatrix, iteration:&IterationTours, context:&UpdateContext) {
+        // Ant System update plus an extra e / L_best on the best-so-far tour
+        AntSystem.global_update(pher_graph, iteration, context);
+        let weight:f32 = self.elitist_weight.unwrap_or(context.cities_list.len() as f32);
+        deposit(pher_graph, iteration.best_tour, weight / iteration.best_distance.max(f32::EPSILON), context.symmetric);
+    }
+}
+
+
+pub struct RankBasedAntSystem {
+    // The best width - 1 ants of the iteration deposit, weighted by rank, plus the best-so-far tour with weight width
+    width: usize,
+}
+
+impl RankBasedAntSystem {
+    pub fn new(width:usize) -> Result<Self, ConfigError> {
+        // Width 1 would leave only the best-so-far tour, that is no longer ranking
+        check_min("width", width, 2)?;
+        Ok(RankBasedAntSystem{width})
+    }
+}
+
+impl Default for RankBasedAntSystem {
+    fn default() -> Self {
+        RankBasedAntSystem{width:6}
+    }
+}
+
+impl PheromoneStrategy for RankBasedAntSystem {
+
+    fn name(&self) -> &'static str {"ras"}
+
+    fn global_update(&self, pher_graph:&mut GraphMatrix, iteration:&IterationTours, context:&UpdateContext) {
+        evaporate(pher_graph, context.config.alpha());
+        let mut ranked:Vec<usize> = (0..iteration.tours.len()).collect();
+        ranked.sort_by(|a, b| iteration.distances[*a].total_cmp(&iteration.distances[*b]));
+        for (rank, tour_idx) in ranked.into_iter().take(self.width.saturating_sub(1)).enumerate() {
+            let weight:f32 = (self.width - 1 - rank) as f32;
+            deposit(pher_graph, &iteration.tours[tour_idx], weight / iteration.distances[tour_idx].max(f32::EPSILON), context.symmetric);
+        }
+        deposit(pher_graph, iteration.best_tour, self.width as f32 / iteration.best_distance.max(f32::EPSILON), context.symmetric);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOUR_A:[usize; 4] = [0, 1, 2, 3];
+    const TOUR_B:[usize; 4] = [0, 2, 1, 3];
+
+    fn updated(strategy:&dyn PheromoneStrategy) -> GraphMatrix {
+        // Four cities with a uniform trail of 1.0, half of which evaporates, tour A has length 10 and tour B 20
+        let cities:Vec<City> = (0..4).map(|i| City{name:i + 1, x:i as f64, y:0.0}).collect();
+        let config:AcoConfig = AcoConfig::builder().alpha(0.5).build().unwrap();
+        let context = UpdateContext{cities_list:&cities, config:&config, symmetric:true};
+        let tours:Vec<Vec<usize>> = vec![TOUR_A.to_vec(), TOUR_B.to_vec()];
+        let iteration = IterationTours{iteration:0, tours:&tours, distances:&[10.0, 20.0], best_tour:&TOUR_A, best_distance:10.0};
+        let mut pher_graph:GraphMatrix = GraphMatrix::from_fn(4, |i, j| if i == j {0.0} else {1.0});
+        strategy.global_update(&mut pher_graph, &iteration, &context);
+        pher_graph
+    }
+
+    fn assert_edges(pher_graph:&GraphMatrix, expected:&[((usize, usize), f32)]) {
+        for ((from_city_idx, to_city_idx), pher) in expected {
+            assert!((pher_graph[(*from_city_idx, *to_city_idx)] - pher).abs() < 1e-6, "edge ({}, {})", from_city_idx, to_city_idx);
+            assert_eq!(pher_graph[(*from_city_idx, *to_city_idx)], pher_graph[(*to_city_idx, *from_city_idx)]);
+        }
+    }
+
+    #[test]
+    fn every_ant_deposits_in_the_ant_system() {
+        // Edges of A gain 0.1, edges of B gain 0.05, edges on both tours gain 0.15
+        assert_edges(&updated(&AntSystem), &[((0, 1), 0.6), ((2, 3), 0.6), ((0, 2), 0.55), ((1, 3), 0.55), ((1, 2), 0.65), ((0, 3), 0.65)]);
+    }
+
+    #[test]
+    fn the_elitist_tour_gets_a_weighted_extra_deposit() {
+        let pher_graph:GraphMatrix = updated(&ElitistAntSystem::new(Some(2.0)).unwrap());
+        assert_edges(&pher_graph, &[((0, 1), 0.8), ((1, 2), 0.85), ((0, 2), 0.55), ((1, 3), 0.55)]);
+        // Without a weight the elitist tour counts once per city
+        let pher_graph:GraphMatrix = updated(&ElitistAntSystem::new(None).unwrap());
+        assert_edges(&pher_graph, &[((0, 1), 1.0), ((0, 2), 0.55)]);
+    }
+
+    #[test]
+    fn only_the_top_ranked_ants_deposit() {
+        // With width 2 only the iteration best ant deposits, with weight 1, plus the best-so-far tour with weight 2
+        assert_edges(&updated(&RankBasedAntSystem::new(2).unwrap()), &[((0, 1), 0.8), ((0, 3), 0.8), ((0, 2), 0.5), ((1, 3), 0.5)]);
+        // With width 3 the second ant deposits with weight 1, the first with weight 2 and the best-so-far tour with weight 3
+        assert_edges(&updated(&RankBasedAntSystem::new(3).unwrap()), &[((0, 1), 1.0), ((0, 2), 0.55), ((1, 2), 1.05)]);
+    }
+
+    #[test]
+    fn invalid_eas_and_ras_parameters_are_rejected() {
+        assert!(matches!(ElitistAntSystem::new(Some(f32::NAN)), Err(ConfigError::NotANumber("elitist_weight"))));
+        assert!(matches!(ElitistAntSystem::new(Some(-1.0)), Err(ConfigError::OutOfRange{name:"elitist_weight", ..})));
+        assert!(matches!(ElitistAntSystem::new(Some(f32::INFINITY)), Err(ConfigError::OutOfRange{name:"elitist_weight", ..})));
+        assert!(ElitistAntSystem::new(Some(0.0)).is_ok());
+        assert!(matches!(RankBasedAntSystem::new(1), Err(ConfigError::TooSmall{name:"width", value:1, min:2})));
+    }
+
+    #[test]
+    fn a_step_update_touches_only_the_traversed_edge() {
+        let dist_graph:GraphMatrix = GraphMatrix::from_fn(5, |i, j| if i == j {0.0} else {(i + j) as f32});
+        for symmetric in [true, false] {
+            let mut pher_graph:GraphMatrix = GraphMatrix::new(5, 1.0);
+            let mut choice_info:ChoiceInfo = ChoiceInfo::new(&pher_graph, &dist_graph, 2.0);
+            StepUpdate{rho:0.1, tau:0.5, symmetric}.apply(&mut pher_graph, &mut choice_info, 1, 3);
+            for i in 0..5 {
+                for j in 0..5 {
+                    let changed:bool = (i, j) == (1, 3) || (symmetric && (i, j) == (3, 1));
+                    assert_eq!(pher_graph[(i, j)], if changed {0.95} else {1.0}, "edge ({}, {})", i, j);
+                }
+            }
+            assert_eq!(choice_info, ChoiceInfo::new(&pher_graph, &dist_graph, 2.0));
+        }
+    }
+
+    #[test]
+    fn only_per_step_acs_updates_during_construction() {
+        let cities:Vec<City> = (0..4).map(|i| City{name:i + 1, x:i as f64, y:0.0}).collect();
+        let config:AcoConfig = AcoConfig::builder().rho(0.2).tau(0.3).build().unwrap();
+        let context = UpdateContext{cities_list:&cities, config:&config, symmetric:true};
+        assert_eq!(AntColonySystem{local_update:LocalUpdate::PerStep, ..Default::default()}.step_update(&context), Some(StepUpdate{rho:0.2, tau:0.3, symmetric:true}));
+        assert_eq!(AntColonySystem{local_update:LocalUpdate::EndOfTour, ..Default::default()}.step_update(&context), None);
+        assert_eq!(AntSystem.step_update(&context), None);
+    }
+
+    #[test]
+    fn the_acs_global_update_only_touches_the_best_tour() {
+        // Edges of A decay to 0.5 and gain 0.5 / 10, the edges only B uses keep their pheromone
+        let pher_graph:GraphMatrix = updated(&AntColonySystem::default());
+        assert_edges(&pher_graph, &[((0, 1), 0.55), ((1, 2), 0.55), ((2, 3), 0.55), ((0, 3), 0.55), ((0, 2), 1.0), ((1, 3), 1.0)]);
+        // Evaporating everything also decays the edges the depositing tour does not use
+        let pher_graph:GraphMatrix = updated(&AntColonySystem{global_update:GlobalUpdate::EvaporateAll, ..Default::default()});
+        assert_edges(&pher_graph, &[((0, 1), 0.55), ((0, 2), 0.5), ((1, 3), 0.5)]);
+    }
+
+    #[test]
+    fn deposit_schedules_pick_the_depositing_tour() {
+        let schedule:DepositSchedule = DepositSchedule::Schedule(3);
+        assert_eq!((0..7).map(|i| schedule.uses_best_so_far(i)).collect::<Vec<bool>>(), vec![false, false, true, false, false, true, false]);
+        assert!(DepositSchedule::BestSoFar.uses_best_so_far(0));
+        assert!(!DepositSchedule::IterationBest.uses_best_so_far(0));
+        // This iteration's best tour is B, the best-so-far tour A was found earlier
+        let tours:Vec<Vec<usize>> = vec![TOUR_B.to_vec(), TOUR_A.to_vec()];
+        let iteration = IterationTours{iteration:2, tours:&tours, distances:&[20.0, 30.0], best_tour:&TOUR_A, best_distance:10.0};
+        assert_eq!(iteration.depositing(DepositSchedule::IterationBest), (&TOUR_B[..], 20.0));
+        assert_eq!(iteration.depositing(DepositSchedule::BestSoFar), (&TOUR_A[..], 10.0));
+        assert_eq!(iteration.depositing(schedule), (&TOUR_A[..], 10.0));
+        assert_eq!(iteration.depositing(DepositSchedule::Schedule(2)), (&TOUR_B[..], 20.0));
+    }
+
+    #[test]
+    fn a_zero_deposit_schedule_is_rejected() {
+        let schedule:DepositSchedule = DepositSchedule::Schedule(0);
+        assert_eq!(schedule.validate(), Err(ConfigError::TooSmall{name:"deposit schedule", value:0, min:1}));
+        assert!(AntColonySystem::new(LocalUpdate::PerStep, GlobalUpdate::BestTourEdges, schedule).is_err());
+        assert!(AntColonySystem::new(LocalUpdate::PerStep, GlobalUpdate::BestTourEdges, DepositSchedule::Schedule(1)).is_ok());
+    }
+}
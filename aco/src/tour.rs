@@ -0,0 +1,122 @@
+use crate::ant3::{GraphMatrix, get_tour_distance_graph};
+use crate::error::AcoError;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tour {
+    cities: Vec<usize>,
+}
+
+impl Tour {
+
+    pub fn new(mut cities:Vec<usize>, num_cities:usize) -> Result<Tour, AcoError> {
+        /* Takes city indicies starting from 0 and checks they visit every one of the
+           num_cities cities exactly once. A closed tour that repeats its first city
+           at the end is accepted and stored without the repeat */
+        if num_cities == 0 {
+            return Err(AcoError::EmptyInstance);
+        }
+        if cities.len() == num_cities + 1 && cities.first() == cities.last() {
+            cities.pop();
+        }
+        let mut seen:Vec<bool> = vec![false; num_cities];
+        for city_idx in cities.iter() {
+            if *city_idx >= num_cities {
+                return Err(AcoError::UnknownCity{city:city_idx + 1, num_cities});
+            }
+            if seen[*city_idx] {
+                return Err(AcoError::NotAPermutation{message:format!("city {} is visited more than once", city_idx + 1)});
+            }
+            seen[*city_idx] = true;
+        }
+        if cities.len() != num_cities {
+            return Err(AcoError::NotAPermutation{message:format!("the tour has {} cities but the instance has {}", cities.len(), num_cities)});
+        }
+        Ok(Tour{cities})
+    }
+
+    // City indicies in visiting order, the edge back to the first city is implied
+    pub fn cities(&self) -> &[usize] {&self.cities}
+
+    pub fn into_cities(self) -> Vec<usize> {
+        self.cities
+    }
+
+    pub fn distance(&self, dist_graph:&GraphMatrix) -> f32 {
+        get_tour_distance_graph(&self.cities, dist_graph)
+    }
+
+    pub fn rotated(&self) -> Tour {
+        // The same cycle starting from city 0
+        let mut cities:Vec<usize> = self.cities.clone();
+        let start:usize = cities.iter().position(|city_idx| *city_idx == 0).unwrap_or(0);
+        cities.rotate_left(start);
+        Tour{cities}
+    }
+
+    pub fn canonical(&self) -> Tour {
+        /* The same cycle starting from city 0 and heading towards the smaller of its two neighbours.
+           Two tours of a symmetric instance have the same edges iff their canonical forms are equal,
+           for asymmetric instances compare rotated() instead as the direction matters */
+        let mut tour:Tour = self.rotated();
+        if tour.cities.len() > 2 && tour.cities[1] > tour.cities[tour.cities.len() - 1] {
+            tour.cities[1..].reverse();
+        }
+        tour
+    }
+
+    pub fn same_cycle(&self, other:&Tour, symmetric:bool) -> bool {
+        // True if both tours use the same edges, in either direction when symmetric is set
+        if symmetric {
+            self.canonical() == other.canonical()
+        } else {
+            self.rotated() == other.rotated()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_accepts_a_permutation_and_drops_the_closing_city() {
+        assert_eq!(Tour::new(vec![2, 0, 1], 3).unwrap().cities(), &[2, 0, 1]);
+        assert_eq!(Tour::new(vec![2, 0, 1, 2], 3).unwrap().cities(), &[2, 0, 1]);
+    }
+
+    #[test]
+    fn new_rejects_tours_that_are_not_permutations() {
+        assert!(matches!(Tour::new(vec![], 0), Err(AcoError::EmptyInstance)));
+        assert!(matches!(Tour::new(vec![0, 1, 3], 3), Err(AcoError::UnknownCity{city:4, num_cities:3})));
+        assert!(matches!(Tour::new(vec![0, 1, 1], 3), Err(AcoError::NotAPermutation{..})));
+        assert!(matches!(Tour::new(vec![0, 1], 3), Err(AcoError::NotAPermutation{..})));
+        assert!(matches!(Tour::new(vec![0, 1, 2, 1], 3), Err(AcoError::NotAPermutation{..})));
+    }
+
+    #[test]
+    fn canonical_starts_at_zero_towards_the_smaller_neighbour() {
+        let tour:Tour = Tour::new(vec![2, 0, 3, 1], 4).unwrap();
+        assert_eq!(tour.rotated().cities(), &[0, 3, 1, 2]);
+        assert_eq!(tour.canonical().cities(), &[0, 2, 1, 3]);
+    }
+
+    #[test]
+    fn same_cycle_ignores_direction_only_when_symmetric() {
+        let tour:Tour = Tour::new(vec![1, 3, 0, 2], 4).unwrap();
+        let reversed:Tour = Tour::new(vec![2, 0, 3, 1], 4).unwrap();
+        let shifted:Tour = Tour::new(vec![0, 2, 1, 3], 4).unwrap();
+        assert!(tour.same_cycle(&reversed, true));
+        assert!(!tour.same_cycle(&reversed, false));
+        assert!(tour.same_cycle(&shifted, false));
+    }
+
+    #[test]
+    fn distance_includes_the_closing_edge() {
+        let rows:[[f32; 3]; 3] = [[0.0, 1.0, 5.0], [2.0, 0.0, 3.0], [4.0, 6.0, 0.0]];
+        let dist_graph:GraphMatrix = GraphMatrix::from_fn(3, |i, j| rows[i][j]);
+        assert_eq!(Tour::new(vec![0, 1, 2], 3).unwrap().distance(&dist_graph), 8.0);
+        assert_eq!(Tour::new(vec![0, 2, 1], 3).unwrap().distance(&dist_graph), 13.0);
+    }
+}
@@ -0,0 +1,367 @@
+use crate::city::City;
+use crate::ant3::{GraphMatrix, get_distance_graph};
+use crate::error::{AcoError, read_file, parse_token, token_column};
+use crate::metric::DistanceMetric;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeWeightType {
+    Euc2d,
+    Ceil2d,
+    Att,
+    Geo,
+    Explicit,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeWeightFormat {
+    FullMatrix,
+    UpperRow,
+    LowerDiagRow,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct TspInstance {
+    pub name: String,
+    pub dimension: usize,
+    pub edge_weight_type: EdgeWeightType,
+    pub metric: Option<DistanceMetric>,
+    pub cities: Vec<City>,
+    pub dist_graph: GraphMatrix,
+}
+
+
+fn unsupported(line_idx:usize, line:&str, value:&str, key:&str) -> AcoError {
+    let column:usize = line.find(value).unwrap_or(0) + 1;
+    AcoError::Parse{line:line_idx + 1, column, message:format!("unsupported {} '{}'", key, value)}
+}
+
+
+fn parse_edge_weight_type(line_idx:usize, line:&str, value:&str) -> Result<EdgeWeightType, AcoError> {
+    match value {
+        "EUC_2D" => Ok(EdgeWeightType::Euc2d),
+        "CEIL_2D" => Ok(EdgeWeightType::Ceil2d),
+        "ATT" => Ok(EdgeWeightType::Att),
+        "GEO" => Ok(EdgeWeightType::Geo),
+        "EXPLICIT" => Ok(EdgeWeightType::Explicit),
+        other => Err(unsupported(line_idx, line, other, "EDGE_WEIGHT_TYPE")),
+    }
+}
+
+
+impl EdgeWeightType {
+    pub fn metric(&self) -> Option<DistanceMetric> {
+        // Returns the distance metric for coordinate based edge weight types
+        match self {
+            EdgeWeightType::Euc2d => Some(DistanceMetric::Nint),
+            EdgeWeightType::Ceil2d => Some(DistanceMetric::Ceil),
+            EdgeWeightType::Att => Some(DistanceMetric::Att),
+            EdgeWeightType::Geo => Some(DistanceMetric::Geo),
+            EdgeWeightType::Explicit => None,
+        }
+    }
+}
+
+
+fn parse_edge_weight_format(line_idx:usize, line:&str, value:&str) -> Result<EdgeWeightFormat, AcoError> {
+    match value {
+        "FULL_MATRIX" => Ok(EdgeWeightFormat::FullMatrix),
+        "UPPER_ROW" => Ok(EdgeWeightFormat::UpperRow),
+        "LOWER_DIAG_ROW" => Ok(EdgeWeightFormat::LowerDiagRow),
+        other => Err(unsupported(line_idx, line, other, "EDGE_WEIGHT_FORMAT")),
+    }
+}
+
+
+fn split_header(line:&str) -> Option<(&str, &str)> {
+    // Splits a "KEY : VALUE" header line, the spaces around the colon are optional
+    let (key, value) = line.split_once(':')?;
+    Some((key.trim(), value.trim()))
+}
+
+
+fn explicit_distance_graph(weights:&[f32], dimension:usize, format:EdgeWeightFormat) -> Result<GraphMatrix, AcoError> {
+    // Fills a distance matrix from the flat list of numbers in an EDGE_WEIGHT_SECTION
+    let expected:usize = match format {
+        EdgeWeightFormat::FullMatrix => dimension * dimension,
+        EdgeWeightFormat::UpperRow => dimension * (dimension - 1) / 2,
+        EdgeWeightFormat::LowerDiagRow => dimension * (dimension + 1) / 2,
+    };
+    if weights.len() != expected {
+        return Err(AcoError::InvalidInstance{
+            message:format!("EDGE_WEIGHT_SECTION has {} values, expected {}", weights.len(), expected)});
+    }
+    /* Full matrices may be directed and are stored row by row as given, the triangular
+       formats describe symmetric instances and only keep one triangle */
+    let mut values = weights.iter();
+    let mut dist_graph:GraphMatrix = match format {
+        EdgeWeightFormat::FullMatrix => GraphMatrix::new(dimension, 0.0),
+        _ => GraphMatrix::new_symmetric(dimension, 0.0),
+    };
+    match format {
+        EdgeWeightFormat::FullMatrix => {
+            for (i, j) in (0..dimension).flat_map(|i| (0..dimension).map(move |j| (i, j))) {
+                dist_graph[(i, j)] = *values.next().unwrap();
+            }
+        },
+        EdgeWeightFormat::UpperRow => {
+            for (i, j) in (0..dimension).flat_map(|i| (i + 1..dimension).map(move |j| (i, j))) {
+                dist_graph[(i, j)] = *values.next().unwrap();
+            }
+        },
+        EdgeWeightFormat::LowerDiagRow => {
+            for (i, j) in (0..dimension).flat_map(|i| (0..=i).map(move |j| (i, j))) {
+                dist_graph[(i, j)] = *values.next().unwrap();
+            }
+        },
+    }
+    Ok(dist_graph)
+}
+
+
+fn parse_coordinate_line(line_idx:usize, line:&str) -> Result<(usize, (f64, f64)), AcoError> {
+    // Parses a "node_id x y" line from NODE_COORD_SECTION or DISPLAY_DATA_SECTION
+    let fields:Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 3 {
+        return Err(AcoError::Parse{line:line_idx + 1, column:line.len() + 1,
+                                   message:"expected a node id followed by x and y".to_string()});
+    }
+    let node_id:usize = parse_token(line_idx, line, fields[0], "node id")?;
+    let x:f64 = parse_token(line_idx, line, fields[1], "x coordinate")?;
+    let y:f64 = parse_token(line_idx, line, fields[2], "y coordinate")?;
+    Ok((node_id, (x, y)))
+}
+
+
+fn missing(message:&str) -> AcoError {
+    AcoError::InvalidInstance{message:message.to_string()}
+}
+
+
+pub fn parse_tsplib(contents:&str) -> Result<TspInstance, AcoError> {
+    /* Parses the contents of a TSPLIB .tsp or .atsp file.
+       Node ids are expected to run from 1 to DIMENSION, city i in the
+       returned instance is node i+1 in the file */
+    let mut name:String = String::new();
+    let mut dimension:Option<usize> = None;
+    let mut edge_weight_type:Option<EdgeWeightType> = None;
+    let mut edge_weight_format:Option<EdgeWeightFormat> = None;
+    let mut coords:Vec<(f64, f64)> = Vec::new();
+    let mut display:Vec<(f64, f64)> = Vec::new();
+    let mut weights:Vec<f32> = Vec::new();
+
+    let mut lines = contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+    while let Some((line_idx, line)) = lines.next() {
+        let trimmed:&str = line.trim();
+        if trimmed == "EOF" {
+            break;
+        }
+        if trimmed.starts_with("NODE_COORD_SECTION") || trimmed.starts_with("DISPLAY_DATA_SECTION") {
+            let n:usize = dimension.ok_or_else(|| missing("DIMENSION must come before the coordinate section"))?;
+            let mut section:Vec<Option<(f64, f64)>> = vec![None; n];
+            for _ in 0..n {
+                let (coord_line_idx, coord_line) = lines.next().ok_or_else(|| missing("coordinate section ended early"))?;
+                let (node_id, coord) = parse_coordinate_line(coord_line_idx, coord_line)?;
+                if node_id < 1 || node_id > n {
+                    return Err(AcoError::Parse{line:coord_line_idx + 1, column:coord_line.find(|c:char| !c.is_whitespace()).unwrap_or(0) + 1,
+                                               message:format!("node id {} is outside 1..={}", node_id, n)});
+                }
+                if section[node_id - 1].replace(coord).is_some() {
+                    return Err(AcoError::DuplicateCity{city:node_id, line:coord_line_idx + 1});
+                }
+            }
+            let section:Vec<(f64, f64)> = section.into_iter().map(|coord| coord.unwrap()).collect();
+            if trimmed.starts_with("NODE_COORD_SECTION") {coords = section} else {display = section}
+        } else if trimmed.starts_with("EDGE_WEIGHT_SECTION") {
+            while let Some((_, next_line)) = lines.peek() {
+                if !next_line.trim().starts_with(|c:char| c.is_ascii_digit() || c == '-' || c == '.') {
+                    break;
+                }
+                let (weight_line_idx, weight_line) = lines.next().unwrap();
+                for value in weight_line.split_whitespace() {
+                    weights.push(parse_token(weight_line_idx, weight_line, value, "edge weight")?);
+                }
+            }
+        } else if let Some((key, value)) = split_header(trimmed) {
+            match key {
+                "NAME" => name = value.to_string(),
+                "DIMENSION" => dimension = Some(parse_token(line_idx, line, value, "DIMENSION")?),
+                "EDGE_WEIGHT_TYPE" => edge_weight_type = Some(parse_edge_weight_type(line_idx, line, value)?),
+                "EDGE_WEIGHT_FORMAT" if value != "FUNCTION" => edge_weight_format = Some(parse_edge_weight_format(line_idx, line, value)?),
+                _ => {},
+            }
+        }
+    }
+
+    let dimension:usize = dimension.ok_or_else(|| missing("missing DIMENSION"))?;
+    if dimension == 0 {
+        return Err(AcoError::EmptyInstance);
+    }
+    let edge_weight_type:EdgeWeightType = edge_weight_type.ok_or_else(|| missing("missing EDGE_WEIGHT_TYPE"))?;
+    let metric:Option<DistanceMetric> = edge_weight_type.metric();
+    // Explicit instances only have coordinates when a display section is given
+    let city_coords:&Vec<(f64, f64)> = if coords.is_empty() {&display} else {&coords};
+    let cities:Vec<City> = (0..dimension).map(|i| {
+        let (x, y) = city_coords.get(i).copied().unwrap_or((0.0, 0.0));
+        City{name:(i+1) as i32, x, y}
+    }).collect();
+    let dist_graph:GraphMatrix = match metric {
+        None => {
+            let format = edge_weight_format.ok_or_else(|| missing("missing EDGE_WEIGHT_FORMAT for EXPLICIT instance"))?;
+            explicit_distance_graph(&weights, dimension, format)?
+        },
+        Some(metric) => {
+            if coords.len() != dimension {
+                return Err(missing("missing NODE_COORD_SECTION"));
+            }
+            get_distance_graph(&cities, metric)
+        },
+    };
+    Ok(TspInstance{name, dimension, edge_weight_type, metric, cities, dist_graph})
+}
+
+
+pub fn read_tsplib(file_path:&str) -> Result<TspInstance, AcoError> {
+    // Takes a file path to a TSPLIB instance and returns the cities and distance matrix
+    let file = read_file(file_path)?;
+    parse_tsplib(&file)
+}
+
+
+pub fn parse_opt_tour(contents:&str) -> Result<Vec<usize>, AcoError> {
+    /*  Parses the TOUR_SECTION of a TSPLIB .opt.tour file
+        The file contains node ids starting from 1, terminated by -1
+        The indicies start from zero
+    */
+    let mut tour:Vec<usize> = Vec::new();
+    let mut in_tour_section:bool = false;
+    for (line_idx, line) in contents.lines().enumerate() {
+        let trimmed:&str = line.trim();
+        if trimmed == "EOF" {
+            break;
+        }
+        if trimmed.starts_with("TOUR_SECTION") {
+            in_tour_section = true;
+            continue;
+        }
+        if !in_tour_section {
+            continue;
+        }
+        for value in line.split_whitespace() {
+            let node_id:i64 = parse_token(line_idx, line, value, "tour node id")?;
+            if node_id == -1 {
+                return Ok(tour);
+            }
+            if node_id < 1 {
+                return Err(AcoError::Parse{line:line_idx + 1, column:token_column(line, value),
+                                           message:format!("tour node ids start from 1, found {}", node_id)});
+            }
+            tour.push((node_id - 1) as usize);
+        }
+    }
+    if !in_tour_section {
+        return Err(missing("missing TOUR_SECTION"));
+    }
+    Ok(tour)
+}
+
+
+pub fn read_opt_tour(file_path:&str) -> Result<Vec<usize>, AcoError> {
+    // Takes a file path to a TSPLIB .opt.tour file and returns the index values of the tour
+    let file = read_file(file_path)?;
+    parse_opt_tour(&file)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explicit(format:&str, section:&str) -> String {
+        format!("NAME : test\nTYPE : TSP\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EXPLICIT\nEDGE_WEIGHT_FORMAT : {}\nEDGE_WEIGHT_SECTION\n{}\nEOF\n", format, section)
+    }
+
+    fn coordinates(edge_weight_type:&str, section:&str) -> String {
+        let dimension:usize = section.lines().count();
+        format!("NAME : test\nDIMENSION : {}\nEDGE_WEIGHT_TYPE : {}\nNODE_COORD_SECTION\n{}\nEOF\n", dimension, edge_weight_type, section)
+    }
+
+    #[test]
+    fn upper_row_fills_both_triangles() {
+        let instance:TspInstance = parse_tsplib(&explicit("UPPER_ROW", "1 2\n3")).unwrap();
+        let dist_graph:&GraphMatrix = &instance.dist_graph;
+        assert!(dist_graph.is_symmetric_storage());
+        assert_eq!((dist_graph[(0, 1)], dist_graph[(0, 2)], dist_graph[(1, 2)]), (1.0, 2.0, 3.0));
+        assert_eq!((dist_graph[(1, 0)], dist_graph[(2, 0)], dist_graph[(2, 1)]), (1.0, 2.0, 3.0));
+        assert!((0..3).all(|i| dist_graph[(i, i)] == 0.0));
+    }
+
+    #[test]
+    fn lower_diag_row_matches_upper_row() {
+        let lower:TspInstance = parse_tsplib(&explicit("LOWER_DIAG_ROW", "0\n1 0\n2 3 0")).unwrap();
+        let upper:TspInstance = parse_tsplib(&explicit("UPPER_ROW", "1 2\n3")).unwrap();
+        assert_eq!(lower.dist_graph, upper.dist_graph);
+    }
+
+    #[test]
+    fn full_matrix_keeps_directed_weights() {
+        let instance:TspInstance = parse_tsplib(&explicit("FULL_MATRIX", "0 1 2\n4 0 3\n5 6 0")).unwrap();
+        let dist_graph:&GraphMatrix = &instance.dist_graph;
+        assert!(!dist_graph.is_symmetric_storage());
+        assert_eq!((dist_graph[(0, 1)], dist_graph[(1, 0)]), (1.0, 4.0));
+        assert_eq!((dist_graph[(1, 2)], dist_graph[(2, 1)]), (3.0, 6.0));
+    }
+
+    #[test]
+    fn euc_2d_rounds_to_the_nearest_integer() {
+        let instance:TspInstance = parse_tsplib(&coordinates("EUC_2D", "1 0 0\n2 1 1\n3 3 4\n4 0 2.5")).unwrap();
+        let dist_graph:&GraphMatrix = &instance.dist_graph;
+        // sqrt(2) rounds down, sqrt(13) rounds up and a half rounds up
+        assert_eq!(dist_graph[(0, 1)], 1.0);
+        assert_eq!(dist_graph[(0, 2)], 5.0);
+        assert_eq!(dist_graph[(1, 2)], 4.0);
+        assert_eq!(dist_graph[(0, 3)], 3.0);
+    }
+
+    #[test]
+    fn geo_matches_burma14() {
+        // The first three cities of burma14, their distances are in the published matrix
+        let instance:TspInstance = parse_tsplib(&coordinates("GEO", "1 16.47 96.10\n2 16.47 94.44\n3 20.09 92.54")).unwrap();
+        let dist_graph:&GraphMatrix = &instance.dist_graph;
+        assert_eq!(dist_graph[(0, 1)], 153.0);
+        assert_eq!(dist_graph[(0, 2)], 510.0);
+        assert_eq!(dist_graph[(1, 2)], 422.0);
+    }
+
+    #[test]
+    fn opt_tour_ids_start_from_one() {
+        let tour:Vec<usize> = parse_opt_tour("NAME : test\nTOUR_SECTION\n1\n3\n2\n-1\nEOF\n").unwrap();
+        assert_eq!(tour, vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn wrong_number_of_weights_is_rejected() {
+        let result = parse_tsplib(&explicit("FULL_MATRIX", "0 1 2\n4 0 3"));
+        assert!(matches!(result, Err(AcoError::InvalidInstance{..})));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_token() {
+        // The bad weight is the second value on the eighth line
+        let error:AcoError = parse_tsplib(&explicit("FULL_MATRIX", "0 1 2\n4 x 3\n5 6 0")).unwrap_err();
+        assert!(matches!(error, AcoError::Parse{line:8, column:3, ..}));
+        assert_eq!(error.to_string(), "line 8, column 3: cannot parse edge weight from 'x'");
+        let error:AcoError = parse_tsplib(&coordinates("EUC_2D", "1 0 0\n  5 1 1")).unwrap_err();
+        assert!(matches!(error, AcoError::Parse{line:6, column:3, ..}));
+        let error:AcoError = parse_tsplib("DIMENSION : 1\nEDGE_WEIGHT_TYPE : MAN_2D\n").unwrap_err();
+        assert!(matches!(error, AcoError::Parse{line:2, column:20, ..}));
+    }
+
+    #[test]
+    fn duplicate_node_id_is_rejected() {
+        let result = parse_tsplib(&coordinates("EUC_2D", "1 0 0\n1 1 1"));
+        assert!(matches!(result, Err(AcoError::DuplicateCity{city:1, line:6})));
+    }
+}